@@ -0,0 +1,157 @@
+// Minting agents: accounts allowed to mint on behalf of the owner, within limits
+use crate::events::{AgentAdded, AgentRemoved};
+use crate::*;
+
+const NANOS_PER_DAY: u64 = 86_400_000_000_000;
+/// Daily mints of the platform agent when `initialize` is given no limits
+const DEFAULT_PLATFORM_DAILY_MINTS: u32 = 100;
+
+/// Limits set by the owner when registering an agent. `None` means unlimited.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AgentLimits {
+    /// Block timestamp (ns) after which the agent can no longer mint
+    pub expires_at: Option<U64>,
+    pub max_total_mints: Option<u32>,
+    pub max_daily_mints: Option<u32>,
+}
+
+#[near(serializers = [borsh])]
+#[derive(Clone, Debug)]
+pub struct Agent {
+    pub limits: AgentLimits,
+    pub total_minted: u32,
+    /// Day number (block timestamp / 1 day) that `minted_today` counts for
+    pub day: u64,
+    pub minted_today: u32,
+    /// ed25519 key the agent signs claim vouchers with
+    pub voucher_key: Option<PublicKey>,
+}
+
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AgentView {
+    pub account_id: AccountId,
+    pub limits: AgentLimits,
+    pub active: bool,
+    pub total_minted: u32,
+    pub minted_today: u32,
+    pub total_remaining: Option<u32>,
+    pub daily_remaining: Option<u32>,
+    pub voucher_key: Option<PublicKey>,
+}
+
+impl AgentLimits {
+    /// Limits of the platform agent `initialize` adds when the owner gives none: a daily cap,
+    /// so a leaked agent key cannot mint the club out in one go
+    pub(crate) fn platform_default() -> Self {
+        Self { max_daily_mints: Some(DEFAULT_PLATFORM_DAILY_MINTS), ..Default::default() }
+    }
+}
+
+impl Agent {
+    pub fn new(limits: AgentLimits) -> Self {
+        Self { limits, total_minted: 0, day: 0, minted_today: 0, voucher_key: None }
+    }
+
+    pub(crate) fn is_expired(&self) -> bool {
+        self.limits.expires_at.is_some_and(|expires_at| env::block_timestamp() >= expires_at.0)
+    }
+
+    fn minted_today(&self) -> u32 {
+        if self.day == env::block_timestamp() / NANOS_PER_DAY {
+            self.minted_today
+        } else {
+            0
+        }
+    }
+}
+
+#[near]
+impl Contract {
+    /// Adds an agent, or sets new limits on an existing one, which keeps its mint counts and
+    /// voucher key
+    #[payable]
+    #[handle_result]
+    pub fn add_authorized_agent(&mut self, agent_id: AccountId, limits: Option<AgentLimits>) -> Result<(), FansError> {
+        self.assert_owner("Only owner can add agents")?;
+        let limits = limits.unwrap_or_default();
+        let agent = match self.internal_agent(&agent_id) {
+            Some(agent) => Agent { limits, ..agent },
+            None => Agent::new(limits),
+        };
+        self.internal_save_agent(&agent_id, &agent);
+        AgentAdded { agent_id }.emit();
+        Ok(())
+    }
+
+    #[payable]
+    #[handle_result]
+    pub fn remove_authorized_agent(&mut self, agent_id: AccountId) -> Result<(), FansError> {
+        self.assert_owner("Only owner can remove agents")?;
+        let legacy = self.legacy_agents.remove(&agent_id).is_some();
+        if self.authorized_agents.remove(&agent_id).is_some() || legacy {
+            AgentRemoved { agent_id }.emit();
+        }
+        Ok(())
+    }
+
+    pub fn is_authorized_agent(&self, account_id: AccountId) -> bool {
+        self.internal_agent(&account_id).is_some_and(|agent| !agent.is_expired())
+    }
+
+    pub fn get_agent(&self, account_id: AccountId) -> Option<AgentView> {
+        self.internal_agent(&account_id).map(|agent| {
+            let minted_today = agent.minted_today();
+            AgentView {
+                active: !agent.is_expired(),
+                total_remaining: agent.limits.max_total_mints.map(|max| max.saturating_sub(agent.total_minted)),
+                daily_remaining: agent.limits.max_daily_mints.map(|max| max.saturating_sub(minted_today)),
+                total_minted: agent.total_minted,
+                minted_today,
+                voucher_key: agent.voucher_key,
+                limits: agent.limits,
+                account_id,
+            }
+        })
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_agent(&self, account_id: &AccountId) -> Option<Agent> {
+        self.authorized_agents.get(account_id).or_else(|| {
+            self.legacy_agents.contains_key(account_id).then(|| Agent::new(AgentLimits::default()))
+        })
+    }
+
+    pub(crate) fn internal_save_agent(&mut self, account_id: &AccountId, agent: &Agent) {
+        self.legacy_agents.remove(account_id);
+        self.authorized_agents.insert(account_id, agent);
+    }
+
+    /// Authorizes a mint by `caller` and counts it against the agent's quotas. The owner is
+    /// never limited. Quota is spent when the mint is requested, so a mint that later fails
+    /// in the callback still counts.
+    pub(crate) fn internal_use_mint_quota(&mut self, caller: &AccountId) -> Result<(), FansError> {
+        if *caller == self.tokens.owner_id {
+            return Ok(());
+        }
+        let mut agent = self.internal_agent(caller).ok_or_else(|| {
+            FansError::Unauthorized("Only contract owner or authorized agents can mint".to_string())
+        })?;
+        if agent.is_expired() {
+            return Err(FansError::Unauthorized("Agent authorization has expired".to_string()));
+        }
+        let minted_today = agent.minted_today();
+        if agent.limits.max_total_mints.is_some_and(|max| agent.total_minted >= max)
+            || agent.limits.max_daily_mints.is_some_and(|max| minted_today >= max)
+        {
+            return Err(FansError::QuotaExceeded);
+        }
+        agent.total_minted += 1;
+        agent.day = env::block_timestamp() / NANOS_PER_DAY;
+        agent.minted_today = minted_today + 1;
+        self.internal_save_agent(caller, &agent);
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+// Vanity numbers: the owner reserves memorable IDs such as fan007, which sequential minting
+// skips, and sells them in English auctions whose winner is minted the ID
+use crate::events::{AuctionCancelled, AuctionSettled, AuctionStarted, BidPlaced, ConfigUpdated};
+use crate::*;
+
+const AUCTION_CALLBACK_GAS: Gas = Gas::from_tgas(10);
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Auction {
+    /// Group the winner's token is minted into
+    pub group_id: String,
+    /// Lowest first bid, in yoctoNEAR; covers the mint's storage and the group price
+    pub starting_price: U128,
+    /// How much each bid has to raise the highest one by, in yoctoNEAR
+    pub min_increment: U128,
+    pub ends_at: U64,
+    pub highest_bidder: Option<AccountId>,
+    /// Held in escrow until the bidder is outbid or the auction is settled
+    pub highest_bid: U128,
+}
+
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuctionView {
+    pub token_id: TokenId,
+    #[serde(flatten)]
+    pub auction: Auction,
+}
+
+#[near]
+impl Contract {
+    /// Holds back `fanNNN` IDs from sequential minting. Each reserved ID takes a slot of the
+    /// supply until it is minted or released.
+    #[payable]
+    #[handle_result]
+    pub fn reserve_ids(&mut self, token_ids: Vec<TokenId>) -> Result<(), FansError> {
+        self.assert_owner("Only owner can reserve IDs")?;
+        for token_id in &token_ids {
+            if self.internal_token_number(token_id).is_none() {
+                return Err(FansError::InvalidReservation(format!("{} is not a fanNNN ID within the supply", token_id)));
+            }
+            if self.tokens.owner_by_id.get(token_id).is_some() {
+                return Err(FansError::InvalidReservation(format!("{} is already minted", token_id)));
+            }
+        }
+        for token_id in &token_ids {
+            if self.reserved_ids.contains(token_id) {
+                continue;
+            }
+            if self.internal_supply_exhausted() {
+                return Err(FansError::SupplyExhausted);
+            }
+            self.burned_ids.remove(token_id);
+            self.reserved_ids.insert(token_id);
+        }
+        ConfigUpdated { field: "reserved_ids".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    /// Returns reserved IDs to sequential minting
+    #[payable]
+    #[handle_result]
+    pub fn release_ids(&mut self, token_ids: Vec<TokenId>) -> Result<(), FansError> {
+        self.assert_owner("Only owner can release IDs")?;
+        for token_id in &token_ids {
+            if !self.reserved_ids.contains(token_id) {
+                return Err(FansError::InvalidReservation(format!("{} is not reserved", token_id)));
+            }
+            if self.auctions.get(token_id).is_some() {
+                return Err(FansError::InvalidReservation(format!("{} is under auction", token_id)));
+            }
+        }
+        for token_id in &token_ids {
+            self.reserved_ids.remove(token_id);
+            // Sequential minting has already skipped past it, so it joins the burned IDs
+            if self.internal_token_number(token_id).is_some_and(|number| number < self.next_token_number) {
+                self.burned_ids.insert(token_id);
+            }
+            self.internal_return_to_draw_pool(token_id);
+        }
+        ConfigUpdated { field: "reserved_ids".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    pub fn get_reserved_ids(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<TokenId> {
+        let start = from_index.map_or(0, |index| index.0 as usize);
+        self.reserved_ids.iter().skip(start).take(limit.map_or(usize::MAX, |limit| limit as usize)).collect()
+    }
+
+    /// Auctions a reserved ID until `ends_at`. The starting price has to cover the mint's
+    /// storage and the group price; whatever the winner bids above that goes to the club.
+    #[payable]
+    #[handle_result]
+    pub fn start_auction(
+        &mut self,
+        token_id: TokenId,
+        group_id: String,
+        starting_price: U128,
+        min_increment: Option<U128>,
+        ends_at: U64,
+    ) -> Result<(), FansError> {
+        self.assert_owner("Only owner can start auctions")?;
+        if !self.reserved_ids.contains(&token_id) {
+            return Err(FansError::InvalidAuction(format!("{} is not reserved", token_id)));
+        }
+        if self.auctions.get(&token_id).is_some() {
+            return Err(FansError::InvalidAuction(format!("{} is already under auction", token_id)));
+        }
+        if ends_at.0 <= env::block_timestamp() {
+            return Err(FansError::InvalidAuction("ends_at must be in the future".to_string()));
+        }
+        let group = self.internal_group_for_mint(&group_id)?;
+        let mint_cost = self.get_mint_storage_cost().0 + group.price.0;
+        if starting_price.0 < mint_cost {
+            return Err(FansError::InvalidAuction(format!("starting price must cover the mint cost of {}", mint_cost)));
+        }
+        let auction = Auction {
+            group_id: group_id.clone(),
+            starting_price,
+            min_increment: min_increment.unwrap_or(U128(0)),
+            ends_at,
+            highest_bidder: None,
+            highest_bid: U128(0),
+        };
+        self.auctions.insert(&token_id, &auction);
+        AuctionStarted { token_id, group_id, starting_price, ends_at }.emit();
+        Ok(())
+    }
+
+    /// Bids the attached deposit. The bid it beats is refunded right away.
+    #[payable]
+    #[handle_result]
+    pub fn place_bid(&mut self, token_id: TokenId) -> Result<(), FansError> {
+        if self.paused {
+            return Err(FansError::Paused);
+        }
+        let mut auction = self.auctions.get(&token_id).ok_or(FansError::UnknownAuction)?;
+        if env::block_timestamp() >= auction.ends_at.0 {
+            return Err(FansError::AuctionEnded);
+        }
+        let bidder_id = env::predecessor_account_id();
+        if self.owns_token(bidder_id.clone()) {
+            return Err(FansError::AlreadyOwns);
+        }
+        self.internal_assert_not_banned(&bidder_id)?;
+        let minimum = match auction.highest_bidder {
+            Some(_) => auction.highest_bid.0 + auction.min_increment.0.max(1),
+            None => auction.starting_price.0,
+        };
+        let amount = env::attached_deposit().as_yoctonear();
+        if amount < minimum {
+            return Err(FansError::BidTooLow { minimum: U128(minimum) });
+        }
+        if let Some(outbid) = auction.highest_bidder.replace(bidder_id.clone()) {
+            Promise::new(outbid).transfer(NearToken::from_yoctonear(auction.highest_bid.0));
+        }
+        auction.highest_bid = U128(amount);
+        self.auctions.insert(&token_id, &auction);
+        BidPlaced { token_id, bidder_id, amount: U128(amount) }.emit();
+        Ok(())
+    }
+
+    /// Closes an ended auction, minting the ID to the highest bidder through the usual mint
+    /// path. Anyone can call it. Without a sale the ID stays reserved and any bid is refunded.
+    #[handle_result]
+    pub fn settle_auction(&mut self, token_id: TokenId) -> Result<PromiseOrValue<Option<Token>>, FansError> {
+        let auction = self.auctions.get(&token_id).ok_or(FansError::UnknownAuction)?;
+        if env::block_timestamp() < auction.ends_at.0 {
+            return Err(FansError::AuctionNotEnded);
+        }
+        if self.paused {
+            return Err(FansError::Paused);
+        }
+        self.auctions.remove(&token_id);
+        let winner_id = match auction.highest_bidder.clone() {
+            Some(winner_id) => winner_id,
+            None => {
+                AuctionSettled { token_id, winner_id: None, amount: U128(0) }.emit();
+                return Ok(PromiseOrValue::Value(None));
+            }
+        };
+        // The winner may have been minted another token or banned since bidding
+        if self.owns_token(winner_id.clone()) || self.internal_assert_not_banned(&winner_id).is_err() {
+            Promise::new(winner_id).transfer(NearToken::from_yoctonear(auction.highest_bid.0));
+            AuctionSettled { token_id, winner_id: None, amount: U128(0) }.emit();
+            return Ok(PromiseOrValue::Value(None));
+        }
+        let group = self.groups.get(&auction.group_id).ok_or_else(|| FansError::UnknownGroup(auction.group_id.clone()))?;
+        let mint_cost = self.get_mint_storage_cost().0 + group.price.0;
+        let deposit = NearToken::from_yoctonear(mint_cost.min(auction.highest_bid.0));
+        Ok(PromiseOrValue::Promise(
+            self.internal_mint_promise(
+                winner_id.clone(),
+                Self::internal_group_metadata(group),
+                auction.group_id.clone(),
+                None,
+                None,
+                Some(token_id.clone()),
+                deposit,
+                winner_id,
+            )
+            .then(Self::ext(env::current_account_id()).with_static_gas(AUCTION_CALLBACK_GAS).on_auction_settled(token_id, auction)),
+        ))
+    }
+
+    /// Calls off an auction nobody has bid on yet; the ID stays reserved
+    #[payable]
+    #[handle_result]
+    pub fn cancel_auction(&mut self, token_id: TokenId) -> Result<(), FansError> {
+        self.assert_owner("Only owner can cancel auctions")?;
+        let auction = self.auctions.get(&token_id).ok_or(FansError::UnknownAuction)?;
+        if auction.highest_bidder.is_some() {
+            return Err(FansError::InvalidAuction("the auction already has bids".to_string()));
+        }
+        self.auctions.remove(&token_id);
+        AuctionCancelled { token_id }.emit();
+        Ok(())
+    }
+
+    pub fn get_auction(&self, token_id: TokenId) -> Option<Auction> {
+        self.auctions.get(&token_id)
+    }
+
+    pub fn get_auctions(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AuctionView> {
+        let start = from_index.map_or(0, |index| index.0 as usize);
+        self.auctions
+            .iter()
+            .skip(start)
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .map(|(token_id, auction)| AuctionView { token_id, auction })
+            .collect()
+    }
+
+    /// Refunds the winner if the mint failed, in which case the ID stays reserved
+    #[private]
+    pub fn on_auction_settled(&mut self, token_id: TokenId, auction: Auction) -> Option<Token> {
+        let token = match env::promise_result(0) {
+            PromiseResult::Successful(value) => serde_json::from_slice::<Token>(&value).ok(),
+            _ => None,
+        };
+        let winner_id = auction.highest_bidder.unwrap();
+        match token {
+            Some(token) => {
+                AuctionSettled { token_id, winner_id: Some(winner_id), amount: auction.highest_bid }.emit();
+                Some(token)
+            }
+            None => {
+                // The mint's deposit came back to the contract with the failed callback
+                Promise::new(winner_id).transfer(NearToken::from_yoctonear(auction.highest_bid.0));
+                AuctionSettled { token_id, winner_id: None, amount: U128(0) }.emit();
+                None
+            }
+        }
+    }
+}
+
+impl Contract {
+    /// The number in a sequential `fanNNN` ID, if it is one within the supply
+    pub(crate) fn internal_token_number(&self, token_id: &str) -> Option<u64> {
+        let number = token_id.strip_prefix("fan")?.parse::<u64>().ok()?;
+        (format!("fan{:03}", number) == token_id && number < self.max_supply as u64).then_some(number)
+    }
+}
@@ -0,0 +1,109 @@
+// Ban list: accounts the owner removed from the club cannot come back through a mint, a gift or
+// a transfer until the ban is lifted or runs out
+use crate::events::{AccountBanned, AccountUnbanned};
+use crate::*;
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ban {
+    pub banned_by: AccountId,
+    pub reason: String,
+    pub since: U64,
+    /// Block timestamp (ns) at which the ban runs out, `None` for a permanent ban
+    pub until: Option<U64>,
+}
+
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BanView {
+    pub account_id: AccountId,
+    #[serde(flatten)]
+    pub ban: Ban,
+}
+
+#[near]
+impl Contract {
+    /// Keeps `account_id` from receiving a token until `until`, or for good. Replaces any
+    /// earlier ban of the account.
+    #[payable]
+    #[handle_result]
+    pub fn ban_account(&mut self, account_id: AccountId, reason: String, until: Option<U64>) -> Result<(), FansError> {
+        self.assert_owner("Only owner can ban accounts")?;
+        self.internal_ban(account_id, reason, until)
+    }
+
+    #[payable]
+    #[handle_result]
+    pub fn unban_account(&mut self, account_id: AccountId) -> Result<(), FansError> {
+        self.assert_owner("Only owner can unban accounts")?;
+        self.bans.remove(&account_id).ok_or(FansError::NotBanned)?;
+        AccountUnbanned { account_id, unbanned_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    /// Burns the token and bans its holder in one call, so they cannot be minted again
+    #[payable]
+    #[handle_result]
+    pub fn burn_and_ban(&mut self, token_id: TokenId, reason: String, until: Option<U64>) -> Result<(), FansError> {
+        self.assert_owner("Only owner can burn and ban")?;
+        let owner_id = self.tokens.owner_by_id.get(&token_id).ok_or(FansError::TokenNotFound)?;
+        self.internal_validate_ban(&owner_id, until)?;
+        self.nft_burn(token_id)?;
+        self.internal_ban(owner_id, reason, until)
+    }
+
+    /// Whether the account is serving a ban; expired bans no longer count
+    pub fn is_banned(&self, account_id: AccountId) -> bool {
+        self.internal_assert_not_banned(&account_id).is_err()
+    }
+
+    pub fn get_ban(&self, account_id: AccountId) -> Option<Ban> {
+        self.bans.get(&account_id)
+    }
+
+    /// Every recorded ban, expired ones included until the owner lifts them
+    pub fn get_bans(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<BanView> {
+        let start = from_index.map_or(0, |index| index.0 as usize);
+        self.bans
+            .iter()
+            .skip(start)
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .map(|(account_id, ban)| BanView { account_id, ban })
+            .collect()
+    }
+
+    pub fn get_ban_count(&self) -> u64 {
+        self.bans.len()
+    }
+}
+
+impl Contract {
+    /// Fails with `Banned` when the account may not receive a token
+    pub(crate) fn internal_assert_not_banned(&self, account_id: &AccountId) -> Result<(), FansError> {
+        match self.bans.get(account_id) {
+            Some(ban) if ban.until.is_none_or(|until| env::block_timestamp() < until.0) => {
+                Err(FansError::Banned(account_id.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn internal_validate_ban(&self, account_id: &AccountId, until: Option<U64>) -> Result<(), FansError> {
+        if *account_id == self.tokens.owner_id {
+            return Err(FansError::InvalidBan("the owner cannot be banned".to_string()));
+        }
+        if until.is_some_and(|until| until.0 <= env::block_timestamp()) {
+            return Err(FansError::InvalidBan("until must be in the future".to_string()));
+        }
+        Ok(())
+    }
+
+    fn internal_ban(&mut self, account_id: AccountId, reason: String, until: Option<U64>) -> Result<(), FansError> {
+        self.internal_validate_ban(&account_id, until)?;
+        let banned_by = env::predecessor_account_id();
+        let ban = Ban { banned_by: banned_by.clone(), reason: reason.clone(), since: U64(env::block_timestamp()), until };
+        self.bans.insert(&account_id, &ban);
+        AccountBanned { account_id, banned_by, until, reason }.emit();
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+// Pending burns: a holder's burn waits out a grace period during which they can take it back,
+// and only then does the token go and its ID return to the pool
+use crate::events::{BurnCancelled, BurnRequested, ConfigUpdated};
+use crate::*;
+
+/// Grace period new clubs start with, in ns (7 days)
+pub(crate) const DEFAULT_BURN_GRACE_PERIOD: u64 = 7 * 86_400_000_000_000;
+/// Burns `finalize_burns` processes when no limit is given; each one syncs every backend
+const DEFAULT_FINALIZE_LIMIT: u32 = 10;
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingBurn {
+    pub owner_id: AccountId,
+    pub requested_at: U64,
+    /// Block timestamp (ns) from which `finalize_burns` may burn the token
+    pub final_after: U64,
+}
+
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingBurnView {
+    pub token_id: TokenId,
+    #[serde(flatten)]
+    pub pending_burn: PendingBurn,
+}
+
+#[near]
+impl Contract {
+    /// Sets how long holders' burns wait before they are final, in ns. 0 makes them immediate.
+    /// Burns already pending keep their original deadline.
+    #[payable]
+    #[handle_result]
+    pub fn set_burn_grace_period(&mut self, grace_period: U64) -> Result<(), FansError> {
+        self.assert_owner("Only owner can set the burn grace period")?;
+        self.burn_grace_period = grace_period.0;
+        ConfigUpdated { field: "burn_grace_period".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    pub fn get_burn_grace_period(&self) -> U64 {
+        U64(self.burn_grace_period)
+    }
+
+    /// Takes back a burn that is still in its grace period
+    #[payable]
+    #[handle_result]
+    pub fn cancel_burn(&mut self, token_id: TokenId) -> Result<(), FansError> {
+        let pending_burn = self.pending_burns.get(&token_id).ok_or(FansError::NoPendingBurn)?;
+        if env::predecessor_account_id() != pending_burn.owner_id {
+            return Err(FansError::Unauthorized("Only the token owner can cancel its burn".to_string()));
+        }
+        self.internal_remove_pending_burn(&token_id);
+        BurnCancelled { owner_id: pending_burn.owner_id, token_id }.emit();
+        Ok(())
+    }
+
+    /// Burns up to `limit` tokens whose grace period is over, earliest deadline first, returning
+    /// how many it burned. Anyone can call it.
+    pub fn finalize_burns(&mut self, limit: Option<u32>) -> u32 {
+        let now = env::block_timestamp();
+        let due: Vec<TokenId> = self
+            .burn_queue
+            .iter()
+            .take_while(|((final_after, _), _)| now >= *final_after)
+            .map(|((_, token_id), _)| token_id)
+            .take(limit.unwrap_or(DEFAULT_FINALIZE_LIMIT) as usize)
+            .collect();
+        let mut burned = 0;
+        for token_id in due {
+            match self.tokens.nft_token(token_id.clone()) {
+                Some(token) => {
+                    self.internal_burn(token, false);
+                    burned += 1;
+                }
+                None => {
+                    self.internal_remove_pending_burn(&token_id);
+                }
+            }
+        }
+        burned
+    }
+
+    pub fn get_pending_burn(&self, token_id: TokenId) -> Option<PendingBurn> {
+        self.pending_burns.get(&token_id)
+    }
+
+    pub fn get_pending_burns(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<PendingBurnView> {
+        let start = from_index.map_or(0, |index| index.0 as usize);
+        self.pending_burns
+            .iter()
+            .skip(start)
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .map(|(token_id, pending_burn)| PendingBurnView { token_id, pending_burn })
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Starts the grace period of a burn the holder asked for
+    pub(crate) fn internal_request_burn(&mut self, token_id: TokenId, owner_id: AccountId) -> Result<(), FansError> {
+        if self.pending_burns.get(&token_id).is_some() {
+            return Err(FansError::BurnPending);
+        }
+        let final_after = U64(env::block_timestamp() + self.burn_grace_period);
+        let pending_burn = PendingBurn { owner_id: owner_id.clone(), requested_at: U64(env::block_timestamp()), final_after };
+        self.pending_burns.insert(&token_id, &pending_burn);
+        self.burn_queue.insert(&(final_after.0, token_id.clone()), &());
+        BurnRequested { owner_id, token_id, final_after }.emit();
+        Ok(())
+    }
+
+    pub(crate) fn internal_remove_pending_burn(&mut self, token_id: &TokenId) -> Option<PendingBurn> {
+        let pending_burn = self.pending_burns.remove(token_id)?;
+        self.burn_queue.remove(&(pending_burn.final_after.0, token_id.clone()));
+        Some(pending_burn)
+    }
+}
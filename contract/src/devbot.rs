@@ -0,0 +1,99 @@
+// The devbot contract, which gates the group chat for token holders and is the authority on
+// which groups exist
+use crate::events::ConfigUpdated;
+use crate::membership::MembershipBackend;
+use crate::*;
+
+#[near_sdk::ext_contract(ext_devbot)]
+pub trait ExtDevbot {
+    fn groups_contains_key(&self, group_id: String) -> bool;
+    fn register_group(&self, group_id: String);
+    fn add_group_member(&self, group_id: String, user_id: AccountId);
+    fn revoke_group_member(&self, group_id: String, user_id: AccountId);
+    fn set_group_member_tier(&self, group_id: String, user_id: AccountId, tier: String);
+}
+
+/// Gas and deposit attached to one cross-contract call
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CallBudget {
+    pub gas: Gas,
+    pub deposit: NearToken,
+}
+
+impl CallBudget {
+    pub(crate) const fn new(tgas: u64, deposit: NearToken) -> Self {
+        Self { gas: Gas::from_tgas(tgas), deposit }
+    }
+}
+
+/// Cost of the devbot-only calls (the group check before a mint, tier changes) and of the
+/// callbacks, tunable by the owner. The membership calls themselves are budgeted per backend,
+/// see [`MembershipBackend`].
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DevbotConfig {
+    /// Gas for the group check before a mint. It is a view, so it takes no deposit.
+    pub groups_contains_key_gas: Gas,
+    pub set_group_member_tier: CallBudget,
+    /// Gas for `nft_mint_callback` itself; the membership fan-out it starts is added on top
+    pub mint_callback_gas: Gas,
+    /// Gas for the callbacks that only record a backend outcome
+    pub sync_callback_gas: Gas,
+}
+
+impl Default for DevbotConfig {
+    fn default() -> Self {
+        Self {
+            groups_contains_key_gas: Gas::from_tgas(5),
+            set_group_member_tier: CallBudget::new(10, NearToken::from_yoctonear(0)),
+            mint_callback_gas: Gas::from_tgas(10),
+            sync_callback_gas: Gas::from_tgas(5),
+        }
+    }
+}
+
+#[near]
+impl Contract {
+    /// Points the contract at a new devbot, which takes over the old one's membership backend
+    /// entry, and registers the club's groups there. Members are not copied over; they are
+    /// added again as they mint.
+    #[payable]
+    #[handle_result]
+    pub fn set_devbot_contract(&mut self, devbot_contract: AccountId) -> Result<(), FansError> {
+        self.assert_owner("Only owner can set the devbot contract")?;
+        let previous = self.devbot_contract.get().unwrap();
+        let backend = match self.membership_backends.iter_mut().find(|backend| backend.contract_id == previous) {
+            Some(backend) => {
+                backend.contract_id = devbot_contract.clone();
+                backend.clone()
+            }
+            None => {
+                let backend = MembershipBackend::devbot(devbot_contract.clone());
+                self.membership_backends.push(backend.clone());
+                backend
+            }
+        };
+        self.devbot_contract.set(&devbot_contract);
+        ConfigUpdated { field: "devbot_contract".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        self.internal_register_groups(&backend);
+        Ok(())
+    }
+
+    pub fn get_devbot_contract(&self) -> AccountId {
+        self.devbot_contract.get().unwrap()
+    }
+
+    #[payable]
+    #[handle_result]
+    pub fn set_devbot_config(&mut self, config: DevbotConfig) -> Result<(), FansError> {
+        self.assert_owner("Only owner can set the devbot config")?;
+        self.devbot_config = config;
+        ConfigUpdated { field: "devbot_config".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    pub fn get_devbot_config(&self) -> DevbotConfig {
+        self.devbot_config.clone()
+    }
+}
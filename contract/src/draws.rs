@@ -0,0 +1,164 @@
+// Random assignment: instead of the next sequential ID, a mint commits to a slot and a reveal in
+// a later block draws its number from the free ones, so no one knows it when asking to mint
+use crate::events::{ConfigUpdated, MintCommitted};
+use crate::*;
+use std::convert::TryInto;
+
+const COMMIT_GAS: Gas = Gas::from_tgas(10);
+/// Drawing itself, on top of the mint the reveal runs
+const DRAW_GAS: Gas = Gas::from_tgas(10);
+const DRAW_CALLBACK_GAS: Gas = Gas::from_tgas(10);
+
+#[near]
+impl Contract {
+    #[payable]
+    #[handle_result]
+    pub fn set_random_assignment(&mut self, enabled: bool) -> Result<(), FansError> {
+        self.assert_owner("Only owner can set random assignment")?;
+        self.random_assignment = enabled;
+        ConfigUpdated { field: "random_assignment".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    pub fn is_random_assignment(&self) -> bool {
+        self.random_assignment
+    }
+
+    /// Mints committed and not yet revealed, each holding a slot of the supply
+    pub fn get_pending_draws(&self) -> u32 {
+        self.pending_draws
+    }
+
+    /// Takes a slot for the mint once the devbot confirmed the group, and reveals its number in
+    /// the next receipt, which runs in a later block
+    #[private]
+    #[payable]
+    pub fn nft_commit_mint(
+        &mut self,
+        token_owner_id: AccountId,
+        token_metadata: TokenMetadata,
+        group_id: String,
+        tier: Option<String>,
+        request_id: Option<String>,
+        payer_id: AccountId,
+    ) -> Promise {
+        self.internal_assert_member_allowed(&token_owner_id);
+        self.internal_assert_group_confirmed(&group_id);
+        self.internal_group_for_mint(&group_id).unwrap_or_else(|err| err.panic());
+        if let Some(tier_id) = &tier {
+            self.internal_tier_for_mint(tier_id).unwrap_or_else(|err| err.panic());
+        }
+        if self.internal_supply_exhausted() {
+            FansError::SupplyExhausted.panic();
+        }
+        self.pending_draws += 1;
+        let committed_at = U64(env::block_height());
+        MintCommitted { owner_id: token_owner_id.clone(), group_id: group_id.clone(), committed_at }.emit();
+        let deposit = env::attached_deposit();
+        Self::ext(env::current_account_id())
+            .with_static_gas(self.internal_mint_callback_gas().saturating_add(DRAW_GAS))
+            .with_attached_deposit(deposit)
+            .nft_reveal_mint(token_owner_id, token_metadata, group_id, tier, request_id, payer_id, committed_at)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(DRAW_CALLBACK_GAS)
+                    .on_draw_revealed(),
+            )
+    }
+
+    /// Draws the committed mint's number with this block's random seed and mints it
+    #[private]
+    #[payable]
+    pub fn nft_reveal_mint(
+        &mut self,
+        token_owner_id: AccountId,
+        token_metadata: TokenMetadata,
+        group_id: String,
+        tier: Option<String>,
+        request_id: Option<String>,
+        payer_id: AccountId,
+        committed_at: U64,
+    ) -> Token {
+        if env::block_height() <= committed_at.0 {
+            env::panic_str("The reveal must run after the commit block");
+        }
+        self.pending_draws = self.pending_draws.saturating_sub(1);
+        // A retried request fails in `nft_mint_callback`, so there is nothing to draw for it
+        let already_minted = request_id.as_ref().is_some_and(|request_id| self.mints_by_request.contains_key(request_id));
+        let token_id = if already_minted { None } else { Some(self.internal_draw_token_id(env::random_seed())) };
+        self.nft_mint_callback(token_owner_id, token_metadata, group_id, tier, request_id, token_id, payer_id)
+    }
+
+    /// Frees the slot if the reveal failed. Its deposit came back to the contract, and whoever
+    /// requested the mint settles the refund when this returns nothing.
+    #[private]
+    pub fn on_draw_revealed(&mut self) -> Option<Token> {
+        if let PromiseResult::Successful(value) = env::promise_result(0) {
+            if let Ok(token) = serde_json::from_slice::<Token>(&value) {
+                return Some(token);
+            }
+        }
+        self.pending_draws = self.pending_draws.saturating_sub(1);
+        None
+    }
+}
+
+impl Contract {
+    /// Gas for the commit and everything it schedules
+    pub(crate) fn internal_commit_gas(&self) -> Gas {
+        COMMIT_GAS
+            .saturating_add(self.internal_mint_callback_gas())
+            .saturating_add(DRAW_GAS)
+            .saturating_add(DRAW_CALLBACK_GAS)
+    }
+
+    /// Picks a free ID uniformly at random from `seed`. Numbers that were minted another way or
+    /// reserved since they entered the pool are dropped from it and the draw goes on.
+    pub(crate) fn internal_draw_token_id(&mut self, mut seed: Vec<u8>) -> TokenId {
+        loop {
+            if self.draw_pool_len == 0 {
+                FansError::SupplyExhausted.panic();
+            }
+            let index = Self::internal_uniform(&mut seed, self.draw_pool_len);
+            let token_id = format!("fan{:03}", self.internal_take_from_draw_pool(index));
+            if !self.reserved_ids.contains(&token_id) && self.tokens.owner_by_id.get(&token_id).is_none() {
+                return token_id;
+            }
+        }
+    }
+
+    /// Puts the ID's number back in the draw pool once its ID is free again
+    pub(crate) fn internal_return_to_draw_pool(&mut self, token_id: &str) {
+        if let Some(number) = self.internal_token_number(token_id) {
+            if self.drawn_numbers.remove(&number) {
+                self.draw_pool.insert(&self.draw_pool_len, &number);
+                self.draw_pool_len += 1;
+            }
+        }
+    }
+
+    /// Removes the pool's `index`th number, moving the last one into its place
+    fn internal_take_from_draw_pool(&mut self, index: u64) -> u64 {
+        let last = self.draw_pool_len - 1;
+        let number = self.draw_pool.get(&index).unwrap_or(index);
+        let last_number = self.draw_pool.remove(&last).unwrap_or(last);
+        if index != last {
+            self.draw_pool.insert(&index, &last_number);
+        }
+        self.draw_pool_len = last;
+        self.drawn_numbers.insert(&number);
+        number
+    }
+
+    /// A number below `bound` with no modulo bias, rehashing `seed` for every attempt
+    fn internal_uniform(seed: &mut Vec<u8>, bound: u64) -> u64 {
+        let limit = (1u128 << 64) / bound as u128 * bound as u128;
+        loop {
+            *seed = env::sha256(seed);
+            let value = u64::from_le_bytes(seed[..8].try_into().unwrap());
+            if (value as u128) < limit {
+                return value % bound;
+            }
+        }
+    }
+}
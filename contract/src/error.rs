@@ -0,0 +1,220 @@
+// Errors returned by the 1000fans contract
+use near_sdk::json_types::U128;
+use near_sdk::{near, AccountId, FunctionError};
+use std::fmt;
+
+/// Every failure surfaced by the contract. Methods with `#[handle_result]` return it as `Err`,
+/// NEP-171 trait methods panic with it. Either way the execution error reads
+/// `<CODE>: <message>`, and clients should match on the code, which never changes between
+/// releases, rather than on the message.
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq, FunctionError)]
+pub enum FansError {
+    /// `NOT_INITIALIZED`: `initialize` has not been called yet
+    NotInitialized,
+    /// `ALREADY_INITIALIZED`: `initialize` can only run once
+    AlreadyInitialized,
+    /// `SUPPLY_EXHAUSTED`: the club's whole supply is minted and no burned ID is free
+    SupplyExhausted,
+    /// `ALREADY_OWNS`: the account (or transfer receiver) already holds a token
+    AlreadyOwns,
+    /// `UNAUTHORIZED`: the caller is not allowed to perform the action
+    Unauthorized(String),
+    /// `LOCKED`: the token cannot move until one year after mint
+    Locked,
+    /// `INSUFFICIENT_DEPOSIT`: the attached deposit does not cover the call, in yoctoNEAR
+    InsufficientDeposit { attached: U128, required: U128 },
+    /// `PAUSED`: the owner has paused minting and transfers
+    Paused,
+    /// `QUOTA_EXCEEDED`: the minting agent has used its total or daily quota
+    QuotaExceeded,
+    /// `UNKNOWN_GROUP`: the group was never created, or the devbot contract does not know it
+    UnknownGroup(String),
+    /// `TOKEN_NOT_FOUND`: no token with this ID exists
+    TokenNotFound,
+    /// `INVALID_METADATA`: the token metadata failed validation
+    InvalidMetadata(String),
+    /// `GROUP_EXISTS`: a group with this ID was already created
+    GroupExists(String),
+    /// `GROUP_FULL`: the group holds as many tokens as its cap allows
+    GroupFull(String),
+    /// `UNKNOWN_TIER`: no tier with this ID was set
+    UnknownTier(String),
+    /// `TIER_FULL`: the tier holds as many tokens as its cap allows
+    TierFull(String),
+    /// `NOT_TRANSFERABLE`: the token's tier does not allow transfers
+    NotTransferable,
+    /// `NOT_AN_UPGRADE`: the target tier is the current one or costs less
+    NotAnUpgrade,
+    /// `INVALID_VOUCHER`: the claim voucher is expired, misaddressed or badly signed
+    InvalidVoucher(String),
+    /// `VOUCHER_USED`: the voucher's nonce was already claimed
+    VoucherUsed,
+    /// `UNKNOWN_GIFT`: no unclaimed gift is held by this key
+    UnknownGift,
+    /// `GIFT_EXISTS`: an unclaimed gift already uses this key
+    GiftExists,
+    /// `GIFT_EXPIRED`: the gift can no longer be claimed, only refunded
+    GiftExpired,
+    /// `GIFT_NOT_EXPIRED`: the gift can still be claimed, so it cannot be refunded yet
+    GiftNotExpired,
+    /// `SPONSOR_BALANCE_TOO_LOW`: the sponsor balance cannot cover the amount, in yoctoNEAR
+    SponsorBalanceTooLow { available: U128, required: U128 },
+    /// `SUSPENDED`: the member is suspended and cannot transfer or approve their token
+    Suspended,
+    /// `NOT_SUSPENDED`: the member has no suspension to lift
+    NotSuspended,
+    /// `INVALID_SUSPENSION`: the member is already suspended, or `until` is not in the future
+    InvalidSuspension(String),
+    /// `BANNED`: the account is banned from the club and cannot receive a token
+    Banned(AccountId),
+    /// `NOT_BANNED`: the account has no ban to lift
+    NotBanned,
+    /// `INVALID_BAN`: the ban targets the owner, or `until` is not in the future
+    InvalidBan(String),
+    /// `BURN_PENDING`: the token is waiting to be burned and cannot move or be burned again
+    BurnPending,
+    /// `NO_PENDING_BURN`: the token has no burn to cancel
+    NoPendingBurn,
+    /// `UNKNOWN_SWAP`: no open swap has this ID
+    UnknownSwap,
+    /// `INVALID_SWAP`: the swap cannot go through, e.g. the offered token changed hands
+    InvalidSwap(String),
+    /// `INVALID_RESERVATION`: the ID is not a free `fanNNN` within the supply, or is under auction
+    InvalidReservation(String),
+    /// `UNKNOWN_AUCTION`: no auction is running for this ID
+    UnknownAuction,
+    /// `INVALID_AUCTION`: the auction cannot be started or cancelled as asked
+    InvalidAuction(String),
+    /// `BID_TOO_LOW`: the bid is under the starting price or the minimum raise, in yoctoNEAR
+    BidTooLow { minimum: U128 },
+    /// `AUCTION_ENDED`: the auction no longer takes bids
+    AuctionEnded,
+    /// `AUCTION_NOT_ENDED`: the auction can only be settled once it has ended
+    AuctionNotEnded,
+    /// `RESALE_CAP_EXCEEDED`: the sale price is above the token's resale cap, in yoctoNEAR
+    ResaleCapExceeded { cap: U128 },
+    /// `INVALID_PAYOUT`: the payout cannot be split as asked
+    InvalidPayout(String),
+    /// `UNKNOWN_OFFER`: no open offer has this ID
+    UnknownOffer,
+    /// `INVALID_OFFER`: the offer cannot be made or accepted as asked
+    InvalidOffer(String),
+    /// `OFFER_EXPIRED`: the offer can no longer be accepted, only cancelled
+    OfferExpired,
+    /// `DUPLICATE_REQUEST`: a mint with this `request_id` already went through
+    DuplicateRequest,
+}
+
+impl FansError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            FansError::NotInitialized => "NOT_INITIALIZED",
+            FansError::AlreadyInitialized => "ALREADY_INITIALIZED",
+            FansError::SupplyExhausted => "SUPPLY_EXHAUSTED",
+            FansError::AlreadyOwns => "ALREADY_OWNS",
+            FansError::Unauthorized(_) => "UNAUTHORIZED",
+            FansError::Locked => "LOCKED",
+            FansError::InsufficientDeposit { .. } => "INSUFFICIENT_DEPOSIT",
+            FansError::Paused => "PAUSED",
+            FansError::QuotaExceeded => "QUOTA_EXCEEDED",
+            FansError::UnknownGroup(_) => "UNKNOWN_GROUP",
+            FansError::TokenNotFound => "TOKEN_NOT_FOUND",
+            FansError::InvalidMetadata(_) => "INVALID_METADATA",
+            FansError::GroupExists(_) => "GROUP_EXISTS",
+            FansError::GroupFull(_) => "GROUP_FULL",
+            FansError::UnknownTier(_) => "UNKNOWN_TIER",
+            FansError::TierFull(_) => "TIER_FULL",
+            FansError::NotTransferable => "NOT_TRANSFERABLE",
+            FansError::NotAnUpgrade => "NOT_AN_UPGRADE",
+            FansError::InvalidVoucher(_) => "INVALID_VOUCHER",
+            FansError::VoucherUsed => "VOUCHER_USED",
+            FansError::UnknownGift => "UNKNOWN_GIFT",
+            FansError::GiftExists => "GIFT_EXISTS",
+            FansError::GiftExpired => "GIFT_EXPIRED",
+            FansError::GiftNotExpired => "GIFT_NOT_EXPIRED",
+            FansError::SponsorBalanceTooLow { .. } => "SPONSOR_BALANCE_TOO_LOW",
+            FansError::Suspended => "SUSPENDED",
+            FansError::NotSuspended => "NOT_SUSPENDED",
+            FansError::InvalidSuspension(_) => "INVALID_SUSPENSION",
+            FansError::Banned(_) => "BANNED",
+            FansError::NotBanned => "NOT_BANNED",
+            FansError::InvalidBan(_) => "INVALID_BAN",
+            FansError::BurnPending => "BURN_PENDING",
+            FansError::NoPendingBurn => "NO_PENDING_BURN",
+            FansError::UnknownSwap => "UNKNOWN_SWAP",
+            FansError::InvalidSwap(_) => "INVALID_SWAP",
+            FansError::InvalidReservation(_) => "INVALID_RESERVATION",
+            FansError::UnknownAuction => "UNKNOWN_AUCTION",
+            FansError::InvalidAuction(_) => "INVALID_AUCTION",
+            FansError::BidTooLow { .. } => "BID_TOO_LOW",
+            FansError::AuctionEnded => "AUCTION_ENDED",
+            FansError::AuctionNotEnded => "AUCTION_NOT_ENDED",
+            FansError::ResaleCapExceeded { .. } => "RESALE_CAP_EXCEEDED",
+            FansError::InvalidPayout(_) => "INVALID_PAYOUT",
+            FansError::UnknownOffer => "UNKNOWN_OFFER",
+            FansError::InvalidOffer(_) => "INVALID_OFFER",
+            FansError::OfferExpired => "OFFER_EXPIRED",
+            FansError::DuplicateRequest => "DUPLICATE_REQUEST",
+        }
+    }
+}
+
+impl fmt::Display for FansError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        match self {
+            FansError::NotInitialized => write!(f, "Contract not initialized"),
+            FansError::AlreadyInitialized => write!(f, "Already initialized"),
+            FansError::SupplyExhausted => write!(f, "Token supply is exhausted"),
+            FansError::AlreadyOwns => write!(f, "Account already owns a token"),
+            FansError::Unauthorized(reason) => write!(f, "{}", reason),
+            FansError::Locked => write!(f, "Transfer not allowed until one year after mint"),
+            FansError::InsufficientDeposit { attached, required } => {
+                write!(f, "Insufficient deposit: attached {}, required {}", attached.0, required.0)
+            }
+            FansError::Paused => write!(f, "Contract is paused"),
+            FansError::QuotaExceeded => write!(f, "Agent mint quota exceeded"),
+            FansError::UnknownGroup(group_id) => write!(f, "Group {} does not exist", group_id),
+            FansError::TokenNotFound => write!(f, "Token not found"),
+            FansError::InvalidMetadata(reason) => write!(f, "Invalid metadata: {}", reason),
+            FansError::GroupExists(group_id) => write!(f, "Group {} already exists", group_id),
+            FansError::GroupFull(group_id) => write!(f, "Group {} is full", group_id),
+            FansError::UnknownTier(tier_id) => write!(f, "Tier {} does not exist", tier_id),
+            FansError::TierFull(tier_id) => write!(f, "Tier {} is full", tier_id),
+            FansError::NotTransferable => write!(f, "Tokens of this tier cannot be transferred"),
+            FansError::NotAnUpgrade => write!(f, "Target tier must cost more than the current one"),
+            FansError::InvalidVoucher(reason) => write!(f, "Invalid voucher: {}", reason),
+            FansError::VoucherUsed => write!(f, "Voucher was already claimed"),
+            FansError::UnknownGift => write!(f, "Gift not found"),
+            FansError::GiftExists => write!(f, "A gift already uses this key"),
+            FansError::GiftExpired => write!(f, "Gift has expired"),
+            FansError::GiftNotExpired => write!(f, "Gift has not expired yet"),
+            FansError::SponsorBalanceTooLow { available, required } => {
+                write!(f, "Sponsor balance too low: available {}, required {}", available.0, required.0)
+            }
+            FansError::Suspended => write!(f, "Member is suspended"),
+            FansError::NotSuspended => write!(f, "Member is not suspended"),
+            FansError::InvalidSuspension(reason) => write!(f, "Invalid suspension: {}", reason),
+            FansError::Banned(account_id) => write!(f, "Account {} is banned", account_id),
+            FansError::NotBanned => write!(f, "Account is not banned"),
+            FansError::InvalidBan(reason) => write!(f, "Invalid ban: {}", reason),
+            FansError::BurnPending => write!(f, "Token is waiting to be burned"),
+            FansError::NoPendingBurn => write!(f, "Token has no pending burn"),
+            FansError::UnknownSwap => write!(f, "Swap not found"),
+            FansError::InvalidSwap(reason) => write!(f, "Invalid swap: {}", reason),
+            FansError::InvalidReservation(reason) => write!(f, "Invalid reservation: {}", reason),
+            FansError::UnknownAuction => write!(f, "Auction not found"),
+            FansError::InvalidAuction(reason) => write!(f, "Invalid auction: {}", reason),
+            FansError::BidTooLow { minimum } => write!(f, "Bid too low: minimum {}", minimum.0),
+            FansError::AuctionEnded => write!(f, "Auction has ended"),
+            FansError::AuctionNotEnded => write!(f, "Auction has not ended yet"),
+            FansError::ResaleCapExceeded { cap } => write!(f, "Price above the resale cap of {}", cap.0),
+            FansError::InvalidPayout(reason) => write!(f, "Invalid payout: {}", reason),
+            FansError::UnknownOffer => write!(f, "Offer not found"),
+            FansError::InvalidOffer(reason) => write!(f, "Invalid offer: {}", reason),
+            FansError::OfferExpired => write!(f, "Offer has expired"),
+            FansError::DuplicateRequest => write!(f, "A mint with this request_id already went through"),
+        }
+    }
+}
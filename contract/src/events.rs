@@ -0,0 +1,373 @@
+//! Events for the `1000fans` standard, logged in the NEP-297 format next to the NEP-171 ones.
+//!
+//! Each event is a typed struct, logged by calling `.emit()` on it, e.g.
+//! `EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"agent_added","data":{"agent_id":"1000fans.near"}}`
+use crate::membership::SyncAction;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::Serialize;
+use near_sdk::{near, AccountId};
+
+/// An account was allowed to mint on behalf of the contract owner
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AgentAdded {
+    pub agent_id: AccountId,
+}
+
+/// A minting agent lost its rights
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AgentRemoved {
+    pub agent_id: AccountId,
+}
+
+/// A membership backend accepted `action` for `account_id` in `group_id`
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GroupSyncSucceeded {
+    pub backend_id: AccountId,
+    pub group_id: String,
+    pub account_id: AccountId,
+    pub action: SyncAction,
+}
+
+/// A membership backend rejected `action` for `account_id` in `group_id`; the token side was kept
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GroupSyncFailed {
+    pub backend_id: AccountId,
+    pub group_id: String,
+    pub account_id: AccountId,
+    pub action: SyncAction,
+}
+
+/// An owner-managed setting changed; `field` names the setting, whose new value can be read
+/// back through its view method
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConfigUpdated {
+    pub field: String,
+    pub updated_by: AccountId,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Paused {
+    pub account_id: AccountId,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Unpaused {
+    pub account_id: AccountId,
+}
+
+/// A token moved to a higher tier, keeping its ID and tenure
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TierUpgraded {
+    pub token_id: String,
+    pub owner_id: AccountId,
+    pub old_tier: Option<String>,
+    pub new_tier: String,
+}
+
+/// A member paid for a membership that whoever holds the gift key can claim
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GiftCreated {
+    pub giver_id: AccountId,
+    pub group_id: String,
+    pub expires_at: U64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GiftClaimed {
+    pub giver_id: AccountId,
+    pub owner_id: AccountId,
+    pub token_id: String,
+}
+
+/// An expired gift was returned to its giver
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GiftRefunded {
+    pub giver_id: AccountId,
+    pub group_id: String,
+}
+
+/// Storage for a relayed call by `account_id` was paid from the sponsor balance
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CallSponsored {
+    pub account_id: AccountId,
+    pub amount: U128,
+}
+
+/// A moderator cut a member's group access until `until`; the member keeps their token
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MemberSuspended {
+    pub account_id: AccountId,
+    pub suspended_by: AccountId,
+    pub until: U64,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MemberReinstated {
+    pub account_id: AccountId,
+    pub reinstated_by: AccountId,
+}
+
+/// The owner banned an account from receiving a token, until `until` or for good
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountBanned {
+    pub account_id: AccountId,
+    pub banned_by: AccountId,
+    pub until: Option<U64>,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountUnbanned {
+    pub account_id: AccountId,
+    pub unbanned_by: AccountId,
+}
+
+/// A member burned their own token with `leave_club` and got `refund` yoctoNEAR of storage back
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MemberLeft {
+    pub account_id: AccountId,
+    pub token_id: String,
+    pub refund: U128,
+}
+
+/// A holder burned their token; it is burned for good once `final_after` has passed
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BurnRequested {
+    pub owner_id: AccountId,
+    pub token_id: String,
+    pub final_after: U64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BurnCancelled {
+    pub owner_id: AccountId,
+    pub token_id: String,
+}
+
+/// A holder offered `token_id` for the counterparty's `counterparty_token_id`
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapProposed {
+    pub swap_id: U64,
+    pub proposer_id: AccountId,
+    pub token_id: String,
+    pub counterparty_id: AccountId,
+    pub counterparty_token_id: String,
+}
+
+/// The two tokens changed hands; each also logs a standard `nft_transfer` event
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapAccepted {
+    pub swap_id: U64,
+    pub proposer_id: AccountId,
+    pub counterparty_id: AccountId,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapCancelled {
+    pub swap_id: U64,
+    pub cancelled_by: AccountId,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionStarted {
+    pub token_id: String,
+    pub group_id: String,
+    pub starting_price: U128,
+    pub ends_at: U64,
+}
+
+/// A bid became the highest; the bid it beat was refunded
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BidPlaced {
+    pub token_id: String,
+    pub bidder_id: AccountId,
+    pub amount: U128,
+}
+
+/// The auction closed. Without a `winner_id` nothing was sold and the ID stays reserved.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionSettled {
+    pub token_id: String,
+    pub winner_id: Option<AccountId>,
+    pub amount: U128,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionCancelled {
+    pub token_id: String,
+}
+
+/// `buyer_id` escrowed `price` for `token_id`, or for any holder's token when it is absent
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferMade {
+    pub offer_id: U64,
+    pub buyer_id: AccountId,
+    pub token_id: Option<String>,
+    pub price: U128,
+    pub expires_at: Option<U64>,
+}
+
+/// The holder sold the token for the escrowed price; a standard `nft_transfer` event is logged too
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferAccepted {
+    pub offer_id: U64,
+    pub token_id: String,
+    pub seller_id: AccountId,
+    pub buyer_id: AccountId,
+    pub price: U128,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferCancelled {
+    pub offer_id: U64,
+    pub cancelled_by: AccountId,
+}
+
+/// A mint took a slot in block `committed_at`; its number is drawn in a later block
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintCommitted {
+    pub owner_id: AccountId,
+    pub group_id: String,
+    pub committed_at: U64,
+}
+
+#[near(event_json(standard = "1000fans"))]
+pub enum FansEvent {
+    #[event_version("1.0.0")]
+    AgentAdded(AgentAdded),
+    #[event_version("1.0.0")]
+    AgentRemoved(AgentRemoved),
+    #[event_version("1.0.0")]
+    GroupSyncSucceeded(GroupSyncSucceeded),
+    #[event_version("1.0.0")]
+    GroupSyncFailed(GroupSyncFailed),
+    #[event_version("1.0.0")]
+    ConfigUpdated(ConfigUpdated),
+    #[event_version("1.0.0")]
+    Paused(Paused),
+    #[event_version("1.0.0")]
+    Unpaused(Unpaused),
+    #[event_version("1.0.0")]
+    TierUpgraded(TierUpgraded),
+    #[event_version("1.0.0")]
+    GiftCreated(GiftCreated),
+    #[event_version("1.0.0")]
+    GiftClaimed(GiftClaimed),
+    #[event_version("1.0.0")]
+    GiftRefunded(GiftRefunded),
+    #[event_version("1.0.0")]
+    CallSponsored(CallSponsored),
+    #[event_version("1.0.0")]
+    MemberSuspended(MemberSuspended),
+    #[event_version("1.0.0")]
+    MemberReinstated(MemberReinstated),
+    #[event_version("1.0.0")]
+    AccountBanned(AccountBanned),
+    #[event_version("1.0.0")]
+    AccountUnbanned(AccountUnbanned),
+    #[event_version("1.0.0")]
+    MemberLeft(MemberLeft),
+    #[event_version("1.0.0")]
+    BurnRequested(BurnRequested),
+    #[event_version("1.0.0")]
+    BurnCancelled(BurnCancelled),
+    #[event_version("1.0.0")]
+    SwapProposed(SwapProposed),
+    #[event_version("1.0.0")]
+    SwapAccepted(SwapAccepted),
+    #[event_version("1.0.0")]
+    SwapCancelled(SwapCancelled),
+    #[event_version("1.0.0")]
+    AuctionStarted(AuctionStarted),
+    #[event_version("1.0.0")]
+    BidPlaced(BidPlaced),
+    #[event_version("1.0.0")]
+    AuctionSettled(AuctionSettled),
+    #[event_version("1.0.0")]
+    AuctionCancelled(AuctionCancelled),
+    #[event_version("1.0.0")]
+    MintCommitted(MintCommitted),
+    #[event_version("1.0.0")]
+    OfferMade(OfferMade),
+    #[event_version("1.0.0")]
+    OfferAccepted(OfferAccepted),
+    #[event_version("1.0.0")]
+    OfferCancelled(OfferCancelled),
+}
+
+macro_rules! impl_emit {
+    ($($name:ident),*) => {
+        $(
+            impl $name {
+                /// Logs the event to the host
+                pub fn emit(self) {
+                    FansEvent::$name(self).emit()
+                }
+            }
+        )*
+    };
+}
+
+impl_emit!(
+    AgentAdded,
+    AgentRemoved,
+    GroupSyncSucceeded,
+    GroupSyncFailed,
+    ConfigUpdated,
+    Paused,
+    Unpaused,
+    TierUpgraded,
+    GiftCreated,
+    GiftClaimed,
+    GiftRefunded,
+    CallSponsored,
+    MemberSuspended,
+    MemberReinstated,
+    AccountBanned,
+    AccountUnbanned,
+    MemberLeft,
+    BurnRequested,
+    BurnCancelled,
+    SwapProposed,
+    SwapAccepted,
+    SwapCancelled,
+    AuctionStarted,
+    BidPlaced,
+    AuctionSettled,
+    AuctionCancelled,
+    MintCommitted,
+    OfferMade,
+    OfferAccepted,
+    OfferCancelled
+);
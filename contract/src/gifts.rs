@@ -0,0 +1,243 @@
+// Gift links: a member pays for a membership up front and hands a private key to a friend, who
+// claims it with that key, onto an existing account or one created for them
+use crate::events::{GiftClaimed, GiftCreated, GiftRefunded};
+use crate::*;
+use near_sdk::Allowance;
+
+/// How long a gift can be claimed before the giver may take it back, in ns (30 days)
+const GIFT_LIFETIME: u64 = 30 * 86_400_000_000_000;
+/// Gas the claim key may burn, paid by the giver. Claims should attach at most 200 TGas.
+const GIFT_CLAIM_ALLOWANCE: NearToken = NearToken::from_millinear(20);
+/// Funds an account created for the recipient, refunded when the gift goes to an existing one
+const NEW_ACCOUNT_DEPOSIT: NearToken = NearToken::from_millinear(10);
+/// Storage for the gift record and its access key
+const GIFT_STORAGE_COST: NearToken = NearToken::from_millinear(3);
+const CREATE_ACCOUNT_GAS: Gas = Gas::from_tgas(50);
+const GIFT_CALLBACK_GAS: Gas = Gas::from_tgas(10);
+
+#[near_sdk::ext_contract(ext_linkdrop)]
+pub trait ExtLinkdrop {
+    fn create_account(&mut self, new_account_id: AccountId, new_public_key: PublicKey);
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gift {
+    pub giver_id: AccountId,
+    pub group_id: String,
+    /// What the giver paid, in yoctoNEAR; whatever the claim does not spend goes back to them
+    pub deposit: U128,
+    pub expires_at: U64,
+}
+
+#[near]
+impl Contract {
+    /// Pays for a membership of `group_id` that the holder of `public_key`'s private key can
+    /// claim. The slot is reserved until the gift is claimed or refunded.
+    #[payable]
+    #[handle_result]
+    pub fn create_gift(&mut self, public_key: PublicKey, group_id: String) -> Result<(), FansError> {
+        if !self.initialized {
+            return Err(FansError::NotInitialized);
+        }
+        if self.paused {
+            return Err(FansError::Paused);
+        }
+        let giver_id = env::predecessor_account_id();
+        if giver_id != self.tokens.owner_id && !self.owns_token(giver_id.clone()) {
+            return Err(FansError::Unauthorized("Only members can give gifts".to_string()));
+        }
+        if self.internal_supply_exhausted() {
+            return Err(FansError::SupplyExhausted);
+        }
+        self.internal_group_for_mint(&group_id)?;
+        if self.gifts.get(&public_key).is_some() {
+            return Err(FansError::GiftExists);
+        }
+        let required = self.get_gift_cost(group_id.clone()).0;
+        let deposit = env::attached_deposit().as_yoctonear();
+        if deposit < required {
+            return Err(FansError::InsufficientDeposit { attached: U128(deposit), required: U128(required) });
+        }
+
+        let expires_at = U64(env::block_timestamp() + GIFT_LIFETIME);
+        let gift = Gift { giver_id: giver_id.clone(), group_id: group_id.clone(), deposit: U128(required), expires_at };
+        self.internal_store_gift(&public_key, &gift);
+        Promise::new(env::current_account_id()).add_access_key_allowance(
+            public_key,
+            Allowance::limited(GIFT_CLAIM_ALLOWANCE).unwrap(),
+            env::current_account_id(),
+            "claim_gift,create_account_and_claim_gift".to_string(),
+        );
+        if deposit > required {
+            Promise::new(giver_id.clone()).transfer(NearToken::from_yoctonear(deposit - required));
+        }
+        GiftCreated { giver_id, group_id, expires_at }.emit();
+        Ok(())
+    }
+
+    /// Claims the gift whose key signed this transaction, minting it to `new_owner`
+    #[handle_result]
+    pub fn claim_gift(&mut self, new_owner: AccountId) -> Result<Promise, FansError> {
+        let (public_key, gift, mint_deposit) = self.internal_take_gift(&new_owner)?;
+        let spent = mint_deposit + GIFT_CLAIM_ALLOWANCE.as_yoctonear();
+        Ok(self.internal_mint_gift(public_key, gift, new_owner, mint_deposit, spent))
+    }
+
+    /// Creates `new_account_id` through the network's linkdrop contract, with `new_public_key`
+    /// as its full access key, then claims the gift whose key signed this transaction onto it
+    #[handle_result]
+    pub fn create_account_and_claim_gift(&mut self, new_account_id: AccountId, new_public_key: PublicKey) -> Result<Promise, FansError> {
+        let (public_key, gift, mint_deposit) = self.internal_take_gift(&new_account_id)?;
+        let spent = mint_deposit + GIFT_CLAIM_ALLOWANCE.as_yoctonear() + NEW_ACCOUNT_DEPOSIT.as_yoctonear();
+        Ok(ext_linkdrop::ext(Self::linkdrop_account())
+            .with_static_gas(CREATE_ACCOUNT_GAS)
+            .with_attached_deposit(NEW_ACCOUNT_DEPOSIT)
+            .create_account(new_account_id.clone(), new_public_key)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(self.internal_mint_gas().saturating_add(GIFT_CALLBACK_GAS.saturating_mul(2)))
+                    .on_gift_account_created(public_key, gift, new_account_id, U128(mint_deposit), U128(spent)),
+            ))
+    }
+
+    /// Returns an expired gift's deposit to its giver and frees its slot
+    #[handle_result]
+    pub fn refund_gift(&mut self, public_key: PublicKey) -> Result<(), FansError> {
+        let gift = self.gifts.get(&public_key).ok_or(FansError::UnknownGift)?;
+        if env::predecessor_account_id() != gift.giver_id {
+            return Err(FansError::Unauthorized("Only the giver can refund a gift".to_string()));
+        }
+        if env::block_timestamp() < gift.expires_at.0 {
+            return Err(FansError::GiftNotExpired);
+        }
+        self.internal_release_gift(&public_key, &gift);
+        Promise::new(env::current_account_id()).delete_key(public_key);
+        Promise::new(gift.giver_id.clone()).transfer(NearToken::from_yoctonear(gift.deposit.0));
+        GiftRefunded { giver_id: gift.giver_id, group_id: gift.group_id }.emit();
+        Ok(())
+    }
+
+    pub fn get_gift(&self, public_key: PublicKey) -> Option<Gift> {
+        self.gifts.get(&public_key)
+    }
+
+    /// Deposit `create_gift` needs for `group_id`: the mint and its price, the claim's gas, a
+    /// new account's funding and the gift's own storage
+    pub fn get_gift_cost(&self, group_id: String) -> U128 {
+        let price = self.groups.get(&group_id).map_or(0, |group| group.price.0);
+        U128(
+            self.get_mint_storage_cost().0
+                + price
+                + GIFT_CLAIM_ALLOWANCE.as_yoctonear()
+                + NEW_ACCOUNT_DEPOSIT.as_yoctonear()
+                + GIFT_STORAGE_COST.as_yoctonear(),
+        )
+    }
+
+    #[private]
+    pub fn on_gift_account_created(&mut self, public_key: PublicKey, gift: Gift, new_account_id: AccountId, mint_deposit: U128, spent: U128) {
+        let created = match env::promise_result(0) {
+            PromiseResult::Successful(value) => serde_json::from_slice::<bool>(&value).unwrap_or(false),
+            _ => false,
+        };
+        if created {
+            self.internal_mint_gift(public_key, gift, new_account_id, mint_deposit.0, spent.0);
+        } else {
+            self.internal_store_gift(&public_key, &gift);
+        }
+    }
+
+    /// Settles a claim: on success the key is deleted and the giver gets back what was not
+    /// spent, otherwise the gift can be claimed again
+    #[private]
+    pub fn on_gift_claimed(&mut self, public_key: PublicKey, gift: Gift, owner_id: AccountId, spent: U128) {
+        let token = match env::promise_result(0) {
+            PromiseResult::Successful(value) => serde_json::from_slice::<Token>(&value).ok(),
+            _ => None,
+        };
+        let token = match token {
+            Some(token) => token,
+            None => return self.internal_store_gift(&public_key, &gift),
+        };
+        Promise::new(env::current_account_id()).delete_key(public_key);
+        let refund = gift.deposit.0.saturating_sub(spent.0);
+        if refund > 0 {
+            Promise::new(gift.giver_id.clone()).transfer(NearToken::from_yoctonear(refund));
+        }
+        GiftClaimed { giver_id: gift.giver_id, owner_id, token_id: token.token_id }.emit();
+    }
+}
+
+impl Contract {
+    /// Removes the gift held by the key that signed this transaction so `new_owner` can claim
+    /// it, returning the key, the gift and what its mint costs today
+    fn internal_take_gift(&mut self, new_owner: &AccountId) -> Result<(PublicKey, Gift, u128), FansError> {
+        // The gift key is an access key on this contract, so claims come from the contract itself
+        if env::predecessor_account_id() != env::current_account_id() {
+            return Err(FansError::Unauthorized("Gifts are claimed with the gift key".to_string()));
+        }
+        if self.paused {
+            return Err(FansError::Paused);
+        }
+        let public_key = env::signer_account_pk();
+        let gift = self.gifts.get(&public_key).ok_or(FansError::UnknownGift)?;
+        if env::block_timestamp() >= gift.expires_at.0 {
+            return Err(FansError::GiftExpired);
+        }
+        if self.owns_token(new_owner.clone()) {
+            return Err(FansError::AlreadyOwns);
+        }
+        self.internal_assert_not_banned(new_owner)?;
+        let price = self.groups.get(&gift.group_id).map_or(0, |group| group.price.0);
+        let mint_deposit = self.get_mint_storage_cost().0 + price;
+        let available = gift.deposit.0 - GIFT_CLAIM_ALLOWANCE.as_yoctonear() - NEW_ACCOUNT_DEPOSIT.as_yoctonear() - GIFT_STORAGE_COST.as_yoctonear();
+        if mint_deposit > available {
+            // The owner raised the group price after the gift was bought
+            return Err(FansError::InsufficientDeposit { attached: U128(available), required: U128(mint_deposit) });
+        }
+        self.internal_release_gift(&public_key, &gift);
+        Ok((public_key, gift, mint_deposit))
+    }
+
+    fn internal_mint_gift(&self, public_key: PublicKey, gift: Gift, owner_id: AccountId, mint_deposit: u128, spent: u128) -> Promise {
+        let group = self.groups.get(&gift.group_id).unwrap();
+        self.internal_mint_promise(
+            owner_id.clone(),
+            Self::internal_group_metadata(group),
+            gift.group_id.clone(),
+            None,
+            None,
+            None,
+            NearToken::from_yoctonear(mint_deposit),
+            gift.giver_id.clone(),
+        )
+        .then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GIFT_CALLBACK_GAS)
+                .on_gift_claimed(public_key, gift, owner_id, U128(spent)),
+        )
+    }
+
+    fn internal_store_gift(&mut self, public_key: &PublicKey, gift: &Gift) {
+        self.gifts.insert(public_key, gift);
+        let reserved = self.gift_reservations.get(&gift.group_id).unwrap_or(0);
+        self.gift_reservations.insert(&gift.group_id, &(reserved + 1));
+        self.pending_gifts += 1;
+    }
+
+    fn internal_release_gift(&mut self, public_key: &PublicKey, gift: &Gift) {
+        self.gifts.remove(public_key);
+        match self.gift_reservations.get(&gift.group_id).unwrap_or(0) {
+            0 | 1 => self.gift_reservations.remove(&gift.group_id),
+            reserved => self.gift_reservations.insert(&gift.group_id, &(reserved - 1)),
+        };
+        self.pending_gifts = self.pending_gifts.saturating_sub(1);
+    }
+
+    /// The top-level account whose linkdrop contract creates accounts, `near` on mainnet
+    fn linkdrop_account() -> AccountId {
+        let current = env::current_account_id();
+        current.as_str().rsplit('.').next().unwrap().parse().unwrap()
+    }
+}
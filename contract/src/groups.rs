@@ -0,0 +1,148 @@
+// Groups: the artist channels of a club. Every token belongs to exactly one group, which has
+// its own cap, price and metadata.
+use crate::events::ConfigUpdated;
+use crate::membership::SyncAction;
+use crate::*;
+
+/// A group as defined by the owner
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub title: String,
+    pub description: Option<String>,
+    pub media: Option<String>,
+    /// sha256 of `media`, required with it
+    pub media_hash: Option<Base64VecU8>,
+    /// Live tokens the group can hold at once; burned tokens free their slot
+    pub max_supply: u32,
+    /// Charged on top of the storage cost when minting into the group, in yoctoNEAR
+    pub price: U128,
+}
+
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupView {
+    pub group_id: String,
+    #[serde(flatten)]
+    pub group: Group,
+    pub supply: U128,
+}
+
+#[near]
+impl Contract {
+    /// Creates a group and registers it on every membership backend
+    #[payable]
+    #[handle_result]
+    pub fn create_group(&mut self, group_id: String, group: Group) -> Result<(), FansError> {
+        self.assert_owner("Only owner can create groups")?;
+        Self::validate_media(&group.media, &group.media_hash)?;
+        if self.groups.get(&group_id).is_some() {
+            return Err(FansError::GroupExists(group_id));
+        }
+        self.groups.insert(&group_id, &group);
+        ConfigUpdated { field: "groups".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        self.internal_sync_membership(&group_id, &env::current_account_id(), SyncAction::Register);
+        Ok(())
+    }
+
+    /// Replaces a group's definition. Lowering `max_supply` below the current supply only
+    /// blocks new mints.
+    #[payable]
+    #[handle_result]
+    pub fn update_group(&mut self, group_id: String, group: Group) -> Result<(), FansError> {
+        self.assert_owner("Only owner can update groups")?;
+        Self::validate_media(&group.media, &group.media_hash)?;
+        if self.groups.get(&group_id).is_none() {
+            return Err(FansError::UnknownGroup(group_id));
+        }
+        self.groups.insert(&group_id, &group);
+        ConfigUpdated { field: "groups".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    pub fn get_group(&self, group_id: String) -> Option<GroupView> {
+        self.groups.get(&group_id).map(|group| self.internal_group_view(group_id, group))
+    }
+
+    pub fn get_groups(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<GroupView> {
+        let start = from_index.map_or(0, |index| index.0 as usize);
+        self.groups
+            .iter()
+            .skip(start)
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .map(|(group_id, group)| self.internal_group_view(group_id, group))
+            .collect()
+    }
+
+    pub fn group_supply(&self, group_id: String) -> U128 {
+        U128(self.tokens_per_group.get(&group_id).map_or(0, |tokens| tokens.len() as u128))
+    }
+
+    pub fn nft_tokens_for_group(&self, group_id: String, from_index: Option<U128>, limit: Option<u64>) -> Vec<Token> {
+        let tokens = match self.tokens_per_group.get(&group_id) {
+            Some(tokens) => tokens,
+            None => return vec![],
+        };
+        let start = from_index.map_or(0, |index| index.0 as usize);
+        tokens
+            .iter()
+            .skip(start)
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .filter_map(|token_id| self.nft_token(token_id))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// The group a mint goes into, if it exists and still has room once unclaimed gifts are
+    /// counted
+    pub(crate) fn internal_group_for_mint(&self, group_id: &str) -> Result<Group, FansError> {
+        let group = self.groups.get(&group_id.to_string()).ok_or_else(|| FansError::UnknownGroup(group_id.to_string()))?;
+        let reserved = self.gift_reservations.get(&group_id.to_string()).unwrap_or(0);
+        if self.group_supply(group_id.to_string()).0 + reserved as u128 >= group.max_supply as u128 {
+            return Err(FansError::GroupFull(group_id.to_string()));
+        }
+        Ok(group)
+    }
+
+    /// Metadata for a token minted straight from the group's definition, as vouchers and gifts do
+    pub(crate) fn internal_group_metadata(group: Group) -> TokenMetadata {
+        TokenMetadata {
+            title: Some(group.title),
+            description: group.description,
+            media: group.media,
+            media_hash: group.media_hash,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    pub(crate) fn internal_add_token_to_group(&mut self, group_id: &str, token_id: &TokenId) {
+        let mut tokens = self.tokens_per_group.get(&group_id.to_string()).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::TokensPerGroupInner { group_hash: env::sha256_array(group_id.as_bytes()) })
+        });
+        tokens.insert(token_id);
+        self.tokens_per_group.insert(&group_id.to_string(), &tokens);
+    }
+
+    pub(crate) fn internal_remove_token_from_group(&mut self, group_id: &str, token_id: &TokenId) {
+        if let Some(mut tokens) = self.tokens_per_group.get(&group_id.to_string()) {
+            tokens.remove(token_id);
+            if tokens.is_empty() {
+                self.tokens_per_group.remove(&group_id.to_string());
+            } else {
+                self.tokens_per_group.insert(&group_id.to_string(), &tokens);
+            }
+        }
+    }
+
+    fn internal_group_view(&self, group_id: String, group: Group) -> GroupView {
+        GroupView { supply: self.group_supply(group_id.clone()), group_id, group }
+    }
+}
@@ -1,15 +1,109 @@
 // A smart contract to mint and check ownership of 1000 fans tokens
+// Mint callbacks carry the whole request, and `#[near]` repeats their arguments in the
+// generated `ext` wrappers, where an item-level allow does not reach
+#![allow(clippy::too_many_arguments)]
 use near_contract_standards::non_fungible_token::approval::NonFungibleTokenApproval;
 use near_contract_standards::non_fungible_token::core::{NonFungibleTokenCore, NonFungibleTokenResolver};
 use near_contract_standards::non_fungible_token::enumeration::NonFungibleTokenEnumeration;
 use near_contract_standards::non_fungible_token::metadata::{NFTContractMetadata, NonFungibleTokenMetadataProvider, TokenMetadata, NFT_METADATA_SPEC};
-use near_contract_standards::non_fungible_token::events::{NftBurn};
+use near_contract_standards::non_fungible_token::events::{NftBurn, NftMint};
 use near_contract_standards::non_fungible_token::NonFungibleToken;
 use near_contract_standards::non_fungible_token::{Token, TokenId};
-use near_sdk::collections::{LazyOption, UnorderedSet, LookupMap};
-use near_sdk::json_types::U128;
-use near_sdk::{env, near, require, AccountId, BorshStorageKey, PanicOnDefault, Promise, PromiseOrValue, PromiseResult, Gas, log, NearToken};
-use near_sdk::serde_json::{self, json};
+use near_sdk::collections::{LazyOption, UnorderedMap, UnorderedSet, LookupMap, LookupSet, TreeMap};
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::{env, near, AccountId, BorshStorageKey, CryptoHash, PublicKey, FunctionError, PanicOnDefault, Promise, PromiseOrValue, PromiseResult, Gas, log, NearToken};
+use near_sdk::serde_json;
+
+mod agents;
+mod auctions;
+mod bans;
+mod burns;
+mod devbot;
+mod draws;
+mod error;
+mod gifts;
+mod groups;
+mod tiers;
+mod vouchers;
+mod membership;
+mod moderation;
+mod offers;
+mod registry;
+mod resale;
+mod sponsorship;
+mod swaps;
+pub mod events;
+
+pub use crate::agents::{Agent, AgentLimits, AgentView};
+pub use crate::auctions::{Auction, AuctionView};
+pub use crate::bans::{Ban, BanView};
+pub use crate::burns::{PendingBurn, PendingBurnView};
+use crate::devbot::ext_devbot;
+pub use crate::devbot::{CallBudget, DevbotConfig, ExtDevbot};
+pub use crate::error::FansError;
+pub use crate::gifts::{ExtLinkdrop, Gift};
+pub use crate::groups::{Group, GroupView};
+pub use crate::tiers::{Tier, TierView, TransferPolicy};
+pub use crate::vouchers::Voucher;
+pub use crate::membership::{MembershipBackend, MembershipSyncView, SyncAction, SyncStatus};
+pub use crate::moderation::Suspension;
+pub use crate::offers::{Offer, OfferView};
+pub use crate::registry::ExtClubRegistry;
+pub use crate::resale::{Payout, ResaleCap};
+pub use crate::swaps::Swap;
+use crate::burns::DEFAULT_BURN_GRACE_PERIOD;
+use crate::events::{AgentAdded, ConfigUpdated, MemberLeft, Paused, Unpaused};
+
+/// Settling a mint `nft_mint` or a voucher requested, refunding it if the callback failed
+const MINT_RESOLVE_GAS: Gas = Gas::from_tgas(10);
+/// `migrate`, run by `upgrade` in the same batch as the deploy
+const MIGRATE_GAS: Gas = Gas::from_tgas(50);
+
+/// Club-specific data attached to every fan token, kept apart from the NEP-177 `extra` string
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FanTokenExtra {
+    pub group_id: String,
+    pub tier: Option<String>,
+    /// Block timestamp (ns) at which the current holder received the token
+    pub held_since: U64,
+    pub flags: u32,
+    /// Group and tier price paid at mint and in tier upgrades, in yoctoNEAR; the base of a
+    /// multiple resale cap
+    pub mint_price: U128,
+}
+
+/// Who paid a token's mint storage, and so gets it back when the holder leaves the club
+#[near(serializers = [borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub enum StoragePayer {
+    Account(AccountId),
+    /// Paid out of the sponsor balance, which it goes back to
+    Sponsor,
+}
+
+/// Upper bounds, in bytes, on the agent-supplied token metadata fields
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetadataLimits {
+    pub max_title_len: u32,
+    pub max_description_len: u32,
+    pub max_media_len: u32,
+    pub max_reference_len: u32,
+    pub max_extra_len: u32,
+}
+
+impl Default for MetadataLimits {
+    fn default() -> Self {
+        Self {
+            max_title_len: 64,
+            max_description_len: 256,
+            max_media_len: 256,
+            max_reference_len: 256,
+            max_extra_len: 256,
+        }
+    }
+}
 
 #[derive(PanicOnDefault)]
 #[near(contract_state)]
@@ -17,10 +111,79 @@ pub struct Contract {
     tokens: NonFungibleToken,
     metadata: LazyOption<NFTContractMetadata>,
     minted_count: u64,
+    /// Number of the next sequential ID, `fan{:03}`; reserved and taken numbers are skipped
+    next_token_number: u64,
     burned_ids: UnorderedSet<String>,
     devbot_contract: LazyOption<AccountId>,
-    authorized_agents: LookupMap<AccountId, bool>,
+    authorized_agents: LookupMap<AccountId, Agent>,
+    /// Agents added before agents had limits, under their old key. Each stays unlimited and
+    /// moves to `authorized_agents` the first time it is written.
+    legacy_agents: LookupMap<AccountId, bool>,
     initialized: bool,
+    token_extra: LookupMap<TokenId, FanTokenExtra>,
+    metadata_limits: MetadataLimits,
+    paused: bool,
+    devbot_config: DevbotConfig,
+    /// Kept inline: the list is short and read on every mint, burn and transfer
+    membership_backends: Vec<MembershipBackend>,
+    /// Last sync outcome per (member, backend)
+    membership_sync: LookupMap<(AccountId, AccountId), SyncStatus>,
+    groups: UnorderedMap<String, Group>,
+    tokens_per_group: LookupMap<String, UnorderedSet<TokenId>>,
+    tiers: UnorderedMap<String, Tier>,
+    tier_supply: LookupMap<String, u32>,
+    max_supply: u32,
+    /// Account allowed to push code upgrades besides the owner
+    factory_id: Option<AccountId>,
+    /// (agent, nonce) of every claimed voucher
+    used_voucher_nonces: LookupSet<(AccountId, u64)>,
+    /// Unclaimed gifts, keyed by the access key that claims them
+    gifts: LookupMap<PublicKey, Gift>,
+    /// Slots held in each group by unclaimed gifts
+    gift_reservations: LookupMap<String, u32>,
+    pending_gifts: u32,
+    /// Part of the contract balance set aside for sponsored calls, in yoctoNEAR
+    sponsor_balance: u128,
+    /// Sponsored calls each account has left
+    sponsored_calls: LookupMap<AccountId, u32>,
+    /// Token minted for each caller-supplied request ID and who it was minted for, so a retried
+    /// mint returns it again while that fan still holds it
+    mints_by_request: LookupMap<String, (TokenId, AccountId)>,
+    moderators: UnorderedSet<AccountId>,
+    /// Suspension history per account, oldest first
+    suspensions: LookupMap<AccountId, Vec<Suspension>>,
+    bans: UnorderedMap<AccountId, Ban>,
+    /// How long a holder's burn waits before `finalize_burns` may process it, in ns
+    burn_grace_period: u64,
+    pending_burns: UnorderedMap<TokenId, PendingBurn>,
+    /// Pending burns ordered by `final_after`, so `finalize_burns` only reads the due ones
+    burn_queue: TreeMap<(u64, TokenId), ()>,
+    swaps: LookupMap<u64, Swap>,
+    next_swap_id: u64,
+    /// IDs held back from sequential minting, to be auctioned
+    reserved_ids: UnorderedSet<TokenId>,
+    auctions: UnorderedMap<TokenId, Auction>,
+    /// Draw IDs at random instead of sequentially
+    random_assignment: bool,
+    /// Numbers the next draw picks from: `0..draw_pool_len`, with moved entries in `draw_pool`
+    draw_pool_len: u64,
+    draw_pool: LookupMap<u64, u64>,
+    /// Numbers taken out of the draw pool, which go back in once their ID is free again
+    drawn_numbers: LookupSet<u64>,
+    /// Mints committed and waiting for their reveal
+    pending_draws: u32,
+    resale_cap: Option<ResaleCap>,
+    /// Open offers, with their price and storage deposit in escrow
+    offers: UnorderedMap<u64, Offer>,
+    /// IDs of the open offers for each token, and of those any holder may accept
+    offers_by_token: LookupMap<TokenId, UnorderedSet<u64>>,
+    open_offers: UnorderedSet<u64>,
+    /// IDs of each buyer's open offers
+    offers_by_account: LookupMap<AccountId, UnorderedSet<u64>>,
+    next_offer_id: u64,
+    /// Who paid each token's mint storage; tokens without an entry refund their holder. The
+    /// entries themselves are paid by the contract.
+    storage_payers: LookupMap<TokenId, StoragePayer>,
 }
 
 #[derive(BorshStorageKey)]
@@ -34,14 +197,49 @@ enum StorageKey {
     BurnedIds,
     DevbotContract,
     AuthorizedAgents,
+    TokenExtra,
+    MembershipSync,
+    Groups,
+    TokensPerGroup,
+    TokensPerGroupInner { group_hash: CryptoHash },
+    Tiers,
+    TierSupply,
+    UsedVoucherNonces,
+    Gifts,
+    GiftReservations,
+    SponsoredCalls,
+    MintsByRequest,
+    Moderators,
+    Suspensions,
+    Bans,
+    PendingBurns,
+    Swaps,
+    ReservedIds,
+    Auctions,
+    DrawPool,
+    DrawnNumbers,
+    Offers,
+    StoragePayers,
+    BurnQueue,
+    OffersByToken,
+    OffersByTokenInner { token_hash: CryptoHash },
+    OpenOffers,
+    OffersByAccount,
+    OffersByAccountInner { account_hash: CryptoHash },
+    Agents,
 }
 
-#[near_sdk::ext_contract(ext_devbot)]
-pub trait ExtDevbot {
-    fn groups_contains_key(&self, group_id: String) -> bool;
-    fn register_group(&self, group_id: String);
-    fn add_group_member(&self, group_id: String, user_id: AccountId);
-    fn revoke_group_member(&self, group_id: String, user_id: AccountId);
+/// State layout of clubs deployed before the upgrade path existed, read once by `migrate`
+#[near(serializers = [borsh])]
+struct ContractV0 {
+    tokens: NonFungibleToken,
+    metadata: LazyOption<NFTContractMetadata>,
+    minted_count: u64,
+    burned_ids: UnorderedSet<String>,
+    devbot_contract: LazyOption<AccountId>,
+    /// Left under `StorageKey::AuthorizedAgents`, where `legacy_agents` reads it
+    _authorized_agents: LookupMap<AccountId, bool>,
+    initialized: bool,
 }
 
 #[near]
@@ -49,49 +247,166 @@ impl Contract {
     #[init]
     #[payable]
     pub fn new(devbot_contract: AccountId) -> Self {
-        require!(!env::state_exists(), "Already initialized");
-        let owner_id = env::current_account_id();
+        Self::new_club(devbot_contract, "1000fans".to_string(), "1000F".to_string(), 1000, env::current_account_id(), None)
+    }
+
+    /// Sets up a club with its own name, symbol, supply and owner. `factory_id`, typically the
+    /// club factory that deploys it, may later push code upgrades.
+    #[init]
+    #[payable]
+    pub fn new_club(
+        devbot_contract: AccountId,
+        name: String,
+        symbol: String,
+        max_supply: u32,
+        owner_id: AccountId,
+        factory_id: Option<AccountId>,
+    ) -> Self {
+        if env::state_exists() {
+            FansError::AlreadyInitialized.panic();
+        }
         let metadata = NFTContractMetadata {
             spec: NFT_METADATA_SPEC.to_string(),
-            name: "1000fans".to_string(),
-            symbol: "1000F".to_string(),
+            name,
+            symbol,
             icon: None,
             base_uri: None,
             reference: None,
             reference_hash: None,
         };
         metadata.assert_valid();
+        let tokens = NonFungibleToken::new(
+            StorageKey::NonFungibleToken,
+            owner_id,
+            Some(StorageKey::TokenMetadata),
+            Some(StorageKey::Enumeration),
+            Some(StorageKey::Approval),
+        );
+        Self::internal_new(
+            tokens,
+            LazyOption::new(StorageKey::Metadata, Some(&metadata)),
+            LazyOption::new(StorageKey::DevbotContract, Some(&devbot_contract)),
+            max_supply,
+            factory_id,
+        )
+    }
+
+    /// Brings a club deployed before this state layout up to date. `upgrade` calls it right
+    /// after deploying, so state already in this layout is returned as is. Tokens minted before
+    /// are not indexed in their group, but still count toward the club's supply.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let state = env::storage_read(b"STATE").unwrap_or_else(|| env::panic_str("No state to migrate"));
+        if let Ok(contract) = borsh::from_slice::<Self>(&state) {
+            return contract;
+        }
+        let old: ContractV0 = borsh::from_slice(&state).unwrap_or_else(|_| env::panic_str("Unknown state layout"));
+        // Collections are taken over rather than rebuilt, `NonFungibleToken::new` would write
+        // a probe token into the existing token tree
+        let mut contract = Self::internal_new(old.tokens, old.metadata, old.devbot_contract, 1000, None);
+        contract.next_token_number = old.minted_count + old.burned_ids.len();
+        contract.minted_count = old.minted_count;
+        contract.burned_ids = old.burned_ids;
+        contract.initialized = old.initialized;
+        if contract.initialized {
+            contract.groups.insert(&Self::default_group_id(), &contract.default_group());
+        }
+        contract
+    }
+}
+
+impl Contract {
+    fn internal_new(
+        tokens: NonFungibleToken,
+        metadata: LazyOption<NFTContractMetadata>,
+        devbot_contract: LazyOption<AccountId>,
+        max_supply: u32,
+        factory_id: Option<AccountId>,
+    ) -> Self {
+        let devbot_id = devbot_contract.get().unwrap();
         Self {
-            tokens: NonFungibleToken::new(
-                StorageKey::NonFungibleToken,
-                owner_id,
-                Some(StorageKey::TokenMetadata),
-                Some(StorageKey::Enumeration),
-                Some(StorageKey::Approval),
-            ),
-            metadata: LazyOption::new(StorageKey::Metadata, Some(&metadata)),
+            tokens,
+            metadata,
             minted_count: 0,
+            next_token_number: 0,
             burned_ids: UnorderedSet::new(StorageKey::BurnedIds),
-            devbot_contract: LazyOption::new(StorageKey::DevbotContract, Some(&devbot_contract)),
-            authorized_agents: LookupMap::new(StorageKey::AuthorizedAgents),
+            devbot_contract,
+            authorized_agents: LookupMap::new(StorageKey::Agents),
+            legacy_agents: LookupMap::new(StorageKey::AuthorizedAgents),
             initialized: false,
+            token_extra: LookupMap::new(StorageKey::TokenExtra),
+            metadata_limits: MetadataLimits::default(),
+            paused: false,
+            devbot_config: DevbotConfig::default(),
+            membership_backends: vec![MembershipBackend::devbot(devbot_id)],
+            membership_sync: LookupMap::new(StorageKey::MembershipSync),
+            groups: UnorderedMap::new(StorageKey::Groups),
+            tokens_per_group: LookupMap::new(StorageKey::TokensPerGroup),
+            tiers: UnorderedMap::new(StorageKey::Tiers),
+            tier_supply: LookupMap::new(StorageKey::TierSupply),
+            max_supply,
+            factory_id,
+            used_voucher_nonces: LookupSet::new(StorageKey::UsedVoucherNonces),
+            gifts: LookupMap::new(StorageKey::Gifts),
+            gift_reservations: LookupMap::new(StorageKey::GiftReservations),
+            pending_gifts: 0,
+            sponsor_balance: 0,
+            sponsored_calls: LookupMap::new(StorageKey::SponsoredCalls),
+            mints_by_request: LookupMap::new(StorageKey::MintsByRequest),
+            moderators: UnorderedSet::new(StorageKey::Moderators),
+            suspensions: LookupMap::new(StorageKey::Suspensions),
+            bans: UnorderedMap::new(StorageKey::Bans),
+            burn_grace_period: DEFAULT_BURN_GRACE_PERIOD,
+            pending_burns: UnorderedMap::new(StorageKey::PendingBurns),
+            burn_queue: TreeMap::new(StorageKey::BurnQueue),
+            swaps: LookupMap::new(StorageKey::Swaps),
+            next_swap_id: 0,
+            reserved_ids: UnorderedSet::new(StorageKey::ReservedIds),
+            auctions: UnorderedMap::new(StorageKey::Auctions),
+            random_assignment: false,
+            draw_pool_len: max_supply as u64,
+            draw_pool: LookupMap::new(StorageKey::DrawPool),
+            drawn_numbers: LookupSet::new(StorageKey::DrawnNumbers),
+            pending_draws: 0,
+            resale_cap: None,
+            offers: UnorderedMap::new(StorageKey::Offers),
+            offers_by_token: LookupMap::new(StorageKey::OffersByToken),
+            open_offers: UnorderedSet::new(StorageKey::OpenOffers),
+            offers_by_account: LookupMap::new(StorageKey::OffersByAccount),
+            next_offer_id: 0,
+            storage_payers: LookupMap::new(StorageKey::StoragePayers),
         }
     }
+}
 
+#[near]
+impl Contract {
     #[payable]
-    pub fn initialize(&mut self) -> Token {
-        require!(!self.initialized, "Already initialized");
+    #[handle_result]
+    pub fn initialize(&mut self, agent_limits: Option<AgentLimits>) -> Result<Token, FansError> {
+        if self.initialized {
+            return Err(FansError::AlreadyInitialized);
+        }
         let contract_id = env::current_account_id().to_string();
-        let group_id = contract_id
-            .strip_suffix(".1000fans.near")
-            .unwrap_or("default")
-            .to_string();
+        let group_id = Self::default_group_id();
         let agent_id = "1000fans.near".parse::<AccountId>().expect("Invalid agent ID");
 
-        // Add 1000fans.near as an authorized agent
-        self.authorized_agents.insert(&agent_id, &true);
+        // Add 1000fans.near as an authorized agent, with a daily cap unless told otherwise
+        self.authorized_agents.insert(&agent_id, &Agent::new(agent_limits.unwrap_or_else(AgentLimits::platform_default)));
+        AgentAdded { agent_id: agent_id.clone() }.emit();
+
+        // The club's own group, which fan000 opens
+        self.groups.insert(&group_id, &self.default_group());
 
         // Mint fan000
+        let extra = FanTokenExtra {
+            group_id: group_id.clone(),
+            tier: None,
+            held_since: U64(env::block_timestamp()),
+            flags: 0,
+            mint_price: U128(0),
+        };
         let token_metadata = TokenMetadata {
             title: Some("1000fans Access Token".to_string()),
             description: Some(format!("Grants access to {}", contract_id)),
@@ -102,82 +417,71 @@ impl Contract {
             expires_at: None,
             starts_at: None,
             updated_at: None,
-            extra: Some(serde_json::to_string(&json!({ "group_id": group_id })).unwrap()),
+            extra: None,
             reference: None,
             reference_hash: None,
         };
         let initial_storage = env::storage_usage();
-        let token = self.tokens.internal_mint(
+        let token = self.tokens.internal_mint_with_refund(
             "fan000".to_string(),
             "1000fans.near".parse().expect("Invalid account ID"),
             Some(token_metadata),
+            None,
         );
+        self.token_extra.insert(&token.token_id, &extra);
+        self.internal_add_token_to_group(&group_id, &token.token_id);
+        let token = self.internal_with_extra(token);
         self.minted_count = 1;
+        self.next_token_number = 1;
 
         // Calculate storage cost and refund excess
         let final_storage = env::storage_usage();
-        let storage_cost = (final_storage - initial_storage) as u128 * env::storage_byte_cost().as_yoctonear() / 100_000;
-        require!(
-            env::attached_deposit().as_yoctonear() >= storage_cost,
-            format!("Insufficient deposit: attached {}, required {}", env::attached_deposit().as_yoctonear(), storage_cost)
-        );
+        let storage_cost = (final_storage - initial_storage) as u128 * env::storage_byte_cost().as_yoctonear();
+        if env::attached_deposit().as_yoctonear() < storage_cost {
+            return Err(FansError::InsufficientDeposit {
+                attached: U128(env::attached_deposit().as_yoctonear()),
+                required: U128(storage_cost),
+            });
+        }
         let refund = env::attached_deposit().as_yoctonear().saturating_sub(storage_cost);
         if refund > 0 {
             Promise::new(env::predecessor_account_id()).transfer(NearToken::from_yoctonear(refund));
         }
         
-        // Chain cross-contract calls: register_group -> add_group_member
-        ext_devbot::ext(self.devbot_contract.get().unwrap())
-            .with_static_gas(Gas::from_tgas(30))
-            .with_attached_deposit(NearToken::from_millinear(10)) // 0.01 NEAR
-            .register_group(group_id.clone())
-            .then(
-                ext_devbot::ext(self.devbot_contract.get().unwrap())
-                    .with_static_gas(Gas::from_tgas(50))
-                    .with_attached_deposit(NearToken::from_millinear(10)) // 0.01 NEAR
-                    .add_group_member(group_id.clone(), agent_id.clone())
-                    .then(
-                        Self::ext(env::current_account_id())
-                            .with_static_gas(Gas::from_tgas(5))
-                            .init_group_callback(group_id, agent_id)
-                    )
-            );
+        // Register the group on every membership backend, then add the agent to it
+        self.internal_register_and_add(&group_id, &agent_id);
 
         self.initialized = true;
 
-        log!("EVENT_JSON:{}", serde_json::json!({
-            "standard": "nep171",
-            "version": "1.0.0",
-            "event": "nft_mint",
-            "data": [{
-                "owner_id": "1000fans.near",
-                "token_ids": ["fan000"]
-            }]
-        }).to_string());
+        NftMint { owner_id: &token.owner_id, token_ids: &[&token.token_id], memo: None }.emit();
 
-        token
+        Ok(token)
     }
 
-    #[private]
-    pub fn init_group_callback(&self, group_id: String, agent_id: AccountId) {
-        if env::promise_results_count() > 0 {
-            for i in 0..env::promise_results_count() {
-                match env::promise_result(i) {
-                    PromiseResult::Successful(_) => log!("Successfully processed step {} for group {} and agent {}", i + 1, group_id, agent_id),
-                    _ => log!("Failed to process step {} for group {} and agent {}, continuing", i + 1, group_id, agent_id),
-                }
-            }
-        } else {
-            log!("No promise results for group {} and agent {}, continuing", group_id, agent_id);
-        }
+    #[payable]
+    #[handle_result]
+    pub fn pause(&mut self) -> Result<(), FansError> {
+        self.assert_owner("Only owner can pause the contract")?;
+        self.paused = true;
+        Paused { account_id: env::predecessor_account_id() }.emit();
+        Ok(())
     }
 
     #[payable]
-    pub fn add_authorized_agent(&mut self, agent_id: AccountId) {
-        assert_eq!(env::predecessor_account_id(), self.tokens.owner_id, "Only owner can add agents");
-        self.authorized_agents.insert(&agent_id, &true);
+    #[handle_result]
+    pub fn unpause(&mut self) -> Result<(), FansError> {
+        self.assert_owner("Only owner can unpause the contract")?;
+        self.paused = false;
+        Unpaused { account_id: env::predecessor_account_id() }.emit();
+        Ok(())
     }
 
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Mints to `token_owner_id`. With a `request_id`, e.g. the payment's onramp session ID,
+    /// repeating the call returns the token already minted for it and refunds the deposit.
     #[payable]
     #[handle_result]
     pub fn nft_mint(
@@ -185,127 +489,410 @@ impl Contract {
         token_owner_id: AccountId,
         token_metadata: TokenMetadata,
         group_id: String,
-    ) -> Result<Token, String> {
-        if !self.initialized {
-            return Err("Contract not initialized".to_string());
-        }
-        if self.minted_count >= 1000 && self.burned_ids.is_empty() {
-            return Err("Cannot mint more than 1000 tokens".to_string());
-        }
-        if self.owns_token(token_owner_id.clone()) {
-            return Err("Account already owns a token".to_string());
-        }
-        let caller = env::predecessor_account_id();
-        if caller != self.tokens.owner_id && !self.authorized_agents.contains_key(&caller) {
-            return Err("Only contract owner or authorized agents can mint".to_string());
-        }
-        let initial_storage = env::storage_usage();
-        let deposit = env::attached_deposit();
-        let storage_cost = self.get_mint_storage_cost().0; // 0.007 NEAR
-        require!(
-            deposit.as_yoctonear() >= storage_cost,
-            format!("Insufficient deposit: attached {}, required {}", deposit.as_yoctonear(), storage_cost)
-        );
-        let _promise = ext_devbot::ext(self.devbot_contract.get().unwrap())
-            .with_static_gas(Gas::from_tgas(5))
-            .groups_contains_key(group_id.clone())
-            .then(
-                Self::ext(env::current_account_id())
-                    .with_static_gas(Gas::from_tgas(10))
-                    .nft_mint_callback(token_owner_id.clone(), token_metadata.clone(), group_id.clone()),
-            );
-        let final_storage = env::storage_usage();
-        let storage_cost = (final_storage - initial_storage) as u128 * env::storage_byte_cost().as_yoctonear() / 100_000;
-        let refund = deposit.as_yoctonear().saturating_sub(storage_cost);
-        if refund > 0 {
-            Promise::new(caller).transfer(NearToken::from_yoctonear(refund));
+        tier: Option<String>,
+        request_id: Option<String>,
+    ) -> Result<PromiseOrValue<Option<Token>>, FansError> {
+        if let Some(request_id) = request_id.as_ref().filter(|request_id| self.mints_by_request.contains_key(request_id)) {
+            // Once burned its ID may belong to someone else, which is not this request's token
+            let token = self.get_mint_by_request(request_id.clone()).ok_or(FansError::TokenNotFound)?;
+            let deposit = env::attached_deposit();
+            if !deposit.is_zero() {
+                Promise::new(env::predecessor_account_id()).transfer(deposit);
+            }
+            return Ok(PromiseOrValue::Value(Some(token)));
         }
-        Ok(self.nft_mint_callback(token_owner_id, token_metadata, group_id))
+        self.internal_request_mint(&env::predecessor_account_id(), token_owner_id, token_metadata, group_id, tier, request_id, None)
+            .map(PromiseOrValue::Promise)
+    }
+
+    /// The token minted for `request_id`, while the fan it was minted for still holds it
+    pub fn get_mint_by_request(&self, request_id: String) -> Option<Token> {
+        let (token_id, owner_id) = self.mints_by_request.get(&request_id)?;
+        self.nft_token(token_id).filter(|token| token.owner_id == owner_id)
     }
 
     #[private]
+    #[payable]
     pub fn nft_mint_callback(
         &mut self,
         token_owner_id: AccountId,
         token_metadata: TokenMetadata,
         group_id: String,
+        tier: Option<String>,
+        request_id: Option<String>,
+        token_id: Option<TokenId>,
+        payer_id: AccountId,
     ) -> Token {
-        // Only check promise results if they exist (skipped in unit tests)
-        let is_valid = if env::promise_results_count() > 0 {
-            assert_eq!(env::promise_results_count(), 1, "Expected one promise result");
-            match env::promise_result(0) {
-                PromiseResult::Successful(value) => serde_json::from_slice(&value).expect("Invalid response"),
-                _ => env::panic_str("Failed to validate group ID"),
-            }
-        } else {
-            true // Assume valid for unit tests
-        };
-        assert!(is_valid, "Group ID does not exist");
+        // A retry that raced the first request past `nft_mint` fails, so `on_mint_resolved`
+        // returns the deposit to the payer and the sponsor balance and answers with the token
+        if request_id.as_ref().is_some_and(|request_id| self.mints_by_request.contains_key(request_id)) {
+            FansError::DuplicateRequest.panic();
+        }
+        self.internal_assert_member_allowed(&token_owner_id);
+        self.internal_assert_group_confirmed(&group_id);
+        // Checked again, another mint may have filled the group while the devbot answered
+        let group = self.internal_group_for_mint(&group_id).unwrap_or_else(|err| err.panic());
+        let tier_def = tier.as_ref().map(|tier_id| self.internal_tier_for_mint(tier_id).unwrap_or_else(|err| err.panic()));
+        let price = group.price.0 + tier_def.as_ref().map_or(0, |tier| tier.price.0);
         let initial_storage = env::storage_usage();
-        let token_id = if !self.burned_ids.is_empty() {
+        // A reserved ID goes to its auction winner and a drawn one to its reveal, other mints
+        // reuse a burned ID if they can
+        let token_id = if let Some(id) = token_id {
+            self.reserved_ids.remove(&id);
+            self.burned_ids.remove(&id);
+            id
+        } else if !self.burned_ids.is_empty() {
             let id = self.burned_ids.iter().next().unwrap();
             self.burned_ids.remove(&id);
-            self.minted_count += 1;
             id
         } else {
-            let id = format!("fan{:03}", self.minted_count);
-            self.minted_count += 1;
-            id
+            self.internal_next_token_id()
+        };
+        self.minted_count += 1;
+        // The agent's `extra` is kept for off-chain readers, but on-chain only the typed record counts
+        let extra = FanTokenExtra {
+            group_id: group_id.clone(),
+            tier: tier.clone(),
+            held_since: U64(env::block_timestamp()),
+            flags: 0,
+            mint_price: U128(price),
         };
         let mut token_metadata = token_metadata;
+        if let Some(tier) = tier_def.filter(|tier| tier.media.is_some()) {
+            token_metadata.media = tier.media;
+            token_metadata.media_hash = tier.media_hash;
+        }
+        token_metadata.copies = Some(1);
         token_metadata.issued_at = Some(env::block_timestamp().to_string());
-        token_metadata.extra = Some(serde_json::to_string(&json!({ "group_id": group_id })).unwrap());
-        let token = self.tokens.internal_mint(token_id.clone(), token_owner_id.clone(), Some(token_metadata));
+        let token = self.tokens.internal_mint_with_refund(token_id.clone(), token_owner_id.clone(), Some(token_metadata), None);
+        self.token_extra.insert(&token_id, &extra);
+        self.internal_add_token_to_group(&group_id, &token_id);
+        if let Some(tier_id) = &tier {
+            self.internal_change_tier_supply(tier_id, true);
+        }
+        if let Some(request_id) = &request_id {
+            self.mints_by_request.insert(request_id, &(token_id.clone(), token_owner_id.clone()));
+        }
+        let token = self.internal_with_extra(token);
         let final_storage = env::storage_usage();
         let storage_cost = (final_storage - initial_storage) as u128 * env::storage_byte_cost().as_yoctonear();
-        // refund excess deposit
-        let refund = env::attached_deposit().as_yoctonear().saturating_sub(storage_cost);
+        if env::attached_deposit().as_yoctonear() < storage_cost + price {
+            FansError::InsufficientDeposit {
+                attached: U128(env::attached_deposit().as_yoctonear()),
+                required: U128(storage_cost + price),
+            }.panic();
+        }
+        // Recorded outside the measured storage: the contract holds the record, so the mint
+        // still fits its fixed storage cost
+        self.storage_payers.insert(&token_id, &StoragePayer::Account(payer_id.clone()));
+        // refund excess deposit to whoever paid for the mint, the group and tier prices stay
+        // with the contract
+        let refund = env::attached_deposit().as_yoctonear().saturating_sub(storage_cost + price);
         if refund > 0 {
-            Promise::new(env::predecessor_account_id()).transfer(NearToken::from_yoctonear(refund));
+            Promise::new(payer_id).transfer(NearToken::from_yoctonear(refund));
         }
-        // Add token owner to group
-        ext_devbot::ext(self.devbot_contract.get().unwrap())
-            .with_static_gas(Gas::from_tgas(50))
-            .with_attached_deposit(NearToken::from_millinear(25)) // 0.025 NEAR
-            .add_group_member(group_id.clone(), token_owner_id.clone())
-            .then(
-                Self::ext(env::current_account_id())
-                    .with_static_gas(Gas::from_tgas(5))
-                    .init_group_callback(group_id, token_owner_id.clone())
-            );
+        self.internal_sync_membership(&group_id, &token_owner_id, SyncAction::Add);
+        self.internal_notify_registry(&token_owner_id, true);
         log!("Storage used: {} bytes, Cost: {} yoctoNEAR, Attached: {}", final_storage - initial_storage, storage_cost, env::attached_deposit().as_yoctonear());
-        log!("EVENT_JSON:{}", serde_json::json!({
-            "standard": "nep171",
-            "version": "1.0.0",
-            "event": "nft_mint",
-            "data": [{
-                "owner_id": token_owner_id,
-                "token_ids": [token_id]
-            }]
-        }).to_string());
+        NftMint { owner_id: &token.owner_id, token_ids: &[&token.token_id], memo: None }.emit();
         token
     }
 
+    /// Settles a mint requested by `nft_mint` or a voucher. A failed callback sent its deposit
+    /// back to the contract, so the payer gets back what they attached, the sponsor balance what
+    /// it covered, and the voucher can be claimed again. A retry that lost the race to its
+    /// `request_id` still gets the token minted for it.
+    #[private]
+    pub fn on_mint_resolved(
+        &mut self,
+        payer_id: AccountId,
+        attached: U128,
+        sponsored: U128,
+        voucher_nonce: Option<(AccountId, U64)>,
+        request_id: Option<String>,
+    ) -> Option<Token> {
+        if let PromiseResult::Successful(value) = env::promise_result(0) {
+            if let Ok(token) = serde_json::from_slice::<Token>(&value) {
+                if sponsored.0 > 0 {
+                    self.storage_payers.insert(&token.token_id, &StoragePayer::Sponsor);
+                }
+                return Some(token);
+            }
+        }
+        if attached.0 > 0 {
+            Promise::new(payer_id).transfer(NearToken::from_yoctonear(attached.0));
+        }
+        self.sponsor_balance += sponsored.0;
+        if let Some((agent_id, nonce)) = voucher_nonce {
+            self.used_voucher_nonces.remove(&(agent_id, nonce.0));
+        }
+        request_id.and_then(|request_id| self.get_mint_by_request(request_id))
+    }
+
     pub fn get_owner(&self) -> AccountId {
         self.tokens.owner_id.clone()
     }
 
-    pub fn is_authorized_agent(&self, account_id: AccountId) -> bool {
-        self.authorized_agents.contains_key(&account_id)
+    pub fn get_max_supply(&self) -> u32 {
+        self.max_supply
+    }
+
+    /// Deploys the wasm passed as raw input over this contract, then migrates the state to the
+    /// new layout. Only the owner or the factory that created the club can upgrade it.
+    #[handle_result]
+    pub fn upgrade(&mut self) -> Result<Promise, FansError> {
+        let caller = env::predecessor_account_id();
+        if caller != self.tokens.owner_id && Some(&caller) != self.factory_id.as_ref() {
+            return Err(FansError::Unauthorized("Only owner or factory can upgrade the contract".to_string()));
+        }
+        let code = env::input().unwrap_or_default();
+        Ok(Promise::new(env::current_account_id()).deploy_contract(code).function_call(
+            "migrate".to_string(),
+            vec![],
+            NearToken::from_yoctonear(0),
+            MIGRATE_GAS,
+        ))
+    }
+
+    #[payable]
+    #[handle_result]
+    pub fn set_metadata_limits(&mut self, limits: MetadataLimits) -> Result<(), FansError> {
+        self.assert_owner("Only owner can set metadata limits")?;
+        self.metadata_limits = limits;
+        ConfigUpdated { field: "metadata_limits".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    pub fn get_metadata_limits(&self) -> MetadataLimits {
+        self.metadata_limits.clone()
+    }
+
+    pub fn get_token_extra(&self, token_id: TokenId) -> Option<FanTokenExtra> {
+        self.token_extra.get(&token_id)
     }
 
     pub fn get_mint_storage_cost(&self) -> U128 {
-        U128(7_000_000_000_000_000_000_000) // 0.007 NEAR
+        U128(11_000_000_000_000_000_000_000) // 0.011 NEAR
     }
 
+    /// Burns the token. A holder's own burn only becomes final once the burn grace period is
+    /// over and `finalize_burns` processes it; until then they can `cancel_burn`. The contract
+    /// owner's burns take effect at once.
     #[payable]
-    pub fn nft_burn(&mut self, token_id: TokenId) {
-        let token = self.tokens.nft_token(token_id.clone()).expect("Token not found");
+    #[handle_result]
+    pub fn nft_burn(&mut self, token_id: TokenId) -> Result<(), FansError> {
+        let token = self.tokens.nft_token(token_id.clone()).ok_or(FansError::TokenNotFound)?;
         let caller = env::predecessor_account_id();
-        require!(
-            caller == token.owner_id || caller == self.tokens.owner_id,
-            "Only the token owner or the contract owner can burn this token"
+        if caller != token.owner_id && caller != self.tokens.owner_id {
+            return Err(FansError::Unauthorized(
+                "Only the token owner or the contract owner can burn this token".to_string(),
+            ));
+        }
+        if caller != self.tokens.owner_id && self.burn_grace_period > 0 {
+            return self.internal_request_burn(token_id, caller);
+        }
+        self.internal_burn(token, caller == self.tokens.owner_id);
+        Ok(())
+    }
+
+    /// Burns the caller's own token and refunds the storage it frees. The refund goes to
+    /// whoever paid the mint storage, which is not always the holder: an agent that minted for
+    /// the fan gets it back, sponsored storage returns to the sponsor balance, and only a fan
+    /// who paid their own mint, or holds a token minted before payers were recorded, is
+    /// refunded directly. The returned amount is the refund wherever it went. The token's ID
+    /// goes back to the pool that later mints draw from. Unlike `nft_burn` there is no grace
+    /// period, leaving is final.
+    #[handle_result]
+    pub fn leave_club(&mut self, token_id: TokenId) -> Result<U128, FansError> {
+        if self.paused {
+            return Err(FansError::Paused);
+        }
+        let token = self.tokens.nft_token(token_id.clone()).ok_or(FansError::TokenNotFound)?;
+        let owner_id = token.owner_id.clone();
+        if env::predecessor_account_id() != owner_id {
+            return Err(FansError::Unauthorized("Only the token owner can leave with it".to_string()));
+        }
+        if self.is_suspended(&owner_id) {
+            return Err(FansError::Suspended);
+        }
+        let payer = self.storage_payers.remove(&token_id).unwrap_or_else(|| StoragePayer::Account(owner_id.clone()));
+        // Offers for the token refund their own storage to their buyers, so they go before the
+        // measurement
+        self.internal_cancel_token_offers(&token_id);
+        let initial_storage = env::storage_usage();
+        self.internal_burn(token, false);
+        let freed = initial_storage.saturating_sub(env::storage_usage());
+        let refund = freed as u128 * env::storage_byte_cost().as_yoctonear();
+        match payer {
+            StoragePayer::Sponsor => self.sponsor_balance += refund,
+            StoragePayer::Account(payer_id) if refund > 0 => {
+                Promise::new(payer_id).transfer(NearToken::from_yoctonear(refund));
+            }
+            StoragePayer::Account(_) => {}
+        }
+        MemberLeft { account_id: owner_id, token_id, refund: U128(refund) }.emit();
+        Ok(U128(refund))
+    }
+
+    pub fn owns_token(&self, account_id: AccountId) -> bool {
+        !self.tokens.nft_tokens_for_owner(account_id, None, Some(1)).is_empty()
+    }
+}
+
+impl Contract {
+    /// Checks a mint of `token_owner_id` counted against `minter`'s quota, then asks the devbot
+    /// whether the group exists before `nft_mint_callback` mints. `on_mint_resolved` refunds the
+    /// caller, and frees `voucher_nonce` of the minter, if the mint fails.
+    pub(crate) fn internal_request_mint(
+        &mut self,
+        minter: &AccountId,
+        token_owner_id: AccountId,
+        token_metadata: TokenMetadata,
+        group_id: String,
+        tier: Option<String>,
+        request_id: Option<String>,
+        voucher_nonce: Option<U64>,
+    ) -> Result<Promise, FansError> {
+        if !self.initialized {
+            return Err(FansError::NotInitialized);
+        }
+        if self.paused {
+            return Err(FansError::Paused);
+        }
+        if self.internal_supply_exhausted() {
+            return Err(FansError::SupplyExhausted);
+        }
+        if self.owns_token(token_owner_id.clone()) {
+            return Err(FansError::AlreadyOwns);
+        }
+        self.internal_assert_not_banned(&token_owner_id)?;
+        let group = self.internal_group_for_mint(&group_id)?;
+        let tier_price = match &tier {
+            Some(tier_id) => self.internal_tier_for_mint(tier_id)?.price.0,
+            None => 0,
+        };
+        self.validate_token_metadata(&token_metadata)?;
+        // A sponsored fan has the storage paid for, prices are always attached
+        let sponsored = self.internal_sponsor_storage(&token_owner_id, self.get_mint_storage_cost().0);
+        let deposit = env::attached_deposit().saturating_add(NearToken::from_yoctonear(sponsored));
+        // 0.011 NEAR plus the group and tier prices
+        let required = self.get_mint_storage_cost().0 + group.price.0 + tier_price;
+        if deposit.as_yoctonear() < required {
+            return Err(FansError::InsufficientDeposit {
+                attached: U128(deposit.as_yoctonear()),
+                required: U128(required),
+            });
+        }
+        self.internal_use_mint_quota(minter)?;
+        let resolve = Self::ext(env::current_account_id()).with_static_gas(MINT_RESOLVE_GAS).on_mint_resolved(
+            env::predecessor_account_id(),
+            U128(env::attached_deposit().as_yoctonear()),
+            U128(sponsored),
+            voucher_nonce.map(|nonce| (minter.clone(), nonce)),
+            request_id.clone(),
         );
+        let payer_id = env::predecessor_account_id();
+        Ok(self.internal_mint_promise(token_owner_id, token_metadata, group_id, tier, request_id, None, deposit, payer_id).then(resolve))
+    }
+
+    /// Asks the devbot whether the group exists, then mints in `nft_mint_callback`, which pays
+    /// storage and prices out of `deposit` and refunds the rest to `payer_id`. In random
+    /// assignment mode mints without a chosen ID go through `nft_commit_mint` instead, which
+    /// draws the ID a block later.
+    pub(crate) fn internal_mint_promise(
+        &self,
+        token_owner_id: AccountId,
+        token_metadata: TokenMetadata,
+        group_id: String,
+        tier: Option<String>,
+        request_id: Option<String>,
+        token_id: Option<TokenId>,
+        deposit: NearToken,
+        payer_id: AccountId,
+    ) -> Promise {
+        // The deposit travels with the callback, which mints and refunds what storage did not use
+        let callback = if token_id.is_none() && self.random_assignment {
+            Self::ext(env::current_account_id())
+                .with_static_gas(self.internal_commit_gas())
+                .with_attached_deposit(deposit)
+                .nft_commit_mint(token_owner_id, token_metadata, group_id.clone(), tier, request_id, payer_id)
+        } else {
+            Self::ext(env::current_account_id())
+                .with_static_gas(self.internal_mint_callback_gas())
+                .with_attached_deposit(deposit)
+                .nft_mint_callback(token_owner_id, token_metadata, group_id.clone(), tier, request_id, token_id, payer_id)
+        };
+        ext_devbot::ext(self.devbot_contract.get().unwrap())
+            .with_static_gas(self.devbot_config.groups_contains_key_gas)
+            .groups_contains_key(group_id)
+            .then(callback)
+    }
+
+    /// Panics if the fan got a token or a ban since the mint was requested. The deposit then
+    /// goes back to the contract, and whoever requested the mint refunds the payer.
+    fn internal_assert_member_allowed(&self, token_owner_id: &AccountId) {
+        if self.owns_token(token_owner_id.clone()) {
+            FansError::AlreadyOwns.panic();
+        }
+        self.internal_assert_not_banned(token_owner_id).unwrap_or_else(|err| err.panic());
+    }
+
+    /// Panics unless the devbot confirmed the group. Without a promise result, as in unit tests
+    /// or a reveal, the group counts as confirmed.
+    fn internal_assert_group_confirmed(&self, group_id: &str) {
+        let is_valid = if env::promise_results_count() > 0 {
+            assert_eq!(env::promise_results_count(), 1, "Expected one promise result");
+            match env::promise_result(0) {
+                PromiseResult::Successful(value) => serde_json::from_slice(&value).unwrap_or(false),
+                _ => false,
+            }
+        } else {
+            true
+        };
+        if !is_valid {
+            FansError::UnknownGroup(group_id.to_string()).panic();
+        }
+    }
+
+    /// Slots held by unclaimed gifts, reserved IDs and mints waiting for their draw count as taken
+    pub(crate) fn internal_supply_exhausted(&self) -> bool {
+        self.minted_count + self.pending_gifts as u64 + self.pending_draws as u64 + self.reserved_ids.len()
+            >= self.max_supply as u64
+    }
+
+    /// Next sequential ID, skipping numbers that are reserved or were already minted to an
+    /// auction winner
+    fn internal_next_token_id(&mut self) -> TokenId {
+        loop {
+            let id = format!("fan{:03}", self.next_token_number);
+            self.next_token_number += 1;
+            if !self.reserved_ids.contains(&id) && self.tokens.owner_by_id.get(&id).is_none() {
+                return id;
+            }
+        }
+    }
+
+    /// Gas `internal_mint_promise` attaches to its calls
+    pub(crate) fn internal_mint_gas(&self) -> Gas {
+        let callback_gas =
+            if self.random_assignment { self.internal_commit_gas() } else { self.internal_mint_callback_gas() };
+        self.devbot_config.groups_contains_key_gas.saturating_add(callback_gas)
+    }
+
+    pub(crate) fn internal_mint_callback_gas(&self) -> Gas {
+        self.devbot_config
+            .mint_callback_gas
+            .saturating_add(self.internal_sync_gas(SyncAction::Add))
+            .saturating_add(self.internal_registry_gas())
+    }
+
+    /// Removes the token for good and returns its ID to the pool later mints draw from
+    pub(crate) fn internal_burn(&mut self, token: Token, by_contract_owner: bool) {
+        let token_id = token.token_id.clone();
+        let extra = self.internal_token_extra(&token);
+        let group_id = extra.group_id;
+        self.internal_remove_pending_burn(&token_id);
+        self.internal_cancel_token_offers(&token_id);
         if let Some(owner) = self.tokens.owner_by_id.remove(&token_id) {
             if let Some(tokens_per_owner) = self.tokens.tokens_per_owner.as_mut() {
                 if let Some(mut tokens) = tokens_per_owner.get(&owner) {
@@ -325,42 +912,182 @@ impl Contract {
             }
             self.tokens.approvals_by_id.as_mut().expect("Approvals should exist").remove(&token_id);
         }
+        self.token_extra.remove(&token_id);
+        self.storage_payers.remove(&token_id);
+        self.internal_remove_token_from_group(&group_id, &token_id);
+        if let Some(tier_id) = &extra.tier {
+            self.internal_change_tier_supply(tier_id, false);
+        }
         self.burned_ids.insert(&token_id);
+        self.internal_return_to_draw_pool(&token_id);
         self.minted_count = self.minted_count.saturating_sub(1);
         NftBurn {
             owner_id: &token.owner_id,
-            authorized_id: if caller == self.tokens.owner_id { Some(&caller) } else { None },
+            authorized_id: if by_contract_owner { Some(&self.tokens.owner_id) } else { None },
             token_ids: &[&token_id],
-            memo: Some(if caller == self.tokens.owner_id {
+            memo: Some(if by_contract_owner {
                 "Burned by contract owner"
             } else {
                 "Burned by token owner"
             }),
         }.emit();
-        let group_id = serde_json::from_str(&token.metadata.unwrap().extra.unwrap())
-            .map(|v: serde_json::Value| v["group_id"].as_str().unwrap().to_string())
-            .expect("Invalid group_id in token metadata");
-        ext_devbot::ext(self.devbot_contract.get().unwrap())
-            .with_static_gas(Gas::from_tgas(10))
-            .revoke_group_member(group_id, token.owner_id.clone())
-            .then(
-                Self::ext(env::current_account_id())
-                    .with_static_gas(Gas::from_tgas(5))
-                    .nft_burn_callback(token_id),
-            );
+        self.internal_sync_membership(&group_id, &token.owner_id, SyncAction::Revoke);
+        self.internal_notify_registry(&token.owner_id, false);
     }
 
-    #[private]
-    pub fn nft_burn_callback(&self, token_id: TokenId) {
-        assert_eq!(env::promise_results_count(), 1, "Expected one promise result");
-        match env::promise_result(0) {
-            PromiseResult::Successful(_) => log!("Successfully revoked group membership for token {}", token_id),
-            _ => log!("Failed to revoke group membership for token {}", token_id),
+    fn assert_owner(&self, reason: &str) -> Result<(), FansError> {
+        if env::predecessor_account_id() != self.tokens.owner_id {
+            return Err(FansError::Unauthorized(reason.to_string()));
         }
+        Ok(())
     }
 
-    pub fn owns_token(&self, account_id: AccountId) -> bool {
-        self.tokens.nft_tokens_for_owner(account_id, None, Some(1)).len() > 0
+    /// Group derived from the contract account, e.g. `theosis` for `theosis.1000fans.near`
+    fn default_group_id() -> String {
+        env::current_account_id()
+            .as_str()
+            .strip_suffix(".1000fans.near")
+            .unwrap_or("default")
+            .to_string()
+    }
+
+    fn default_group(&self) -> Group {
+        Group {
+            title: "1000fans Access Token".to_string(),
+            description: Some(format!("Grants access to {}", env::current_account_id())),
+            media: None,
+            media_hash: None,
+            max_supply: self.max_supply,
+            price: U128(0),
+        }
+    }
+
+    /// NEP-177: a hash is only meaningful next to the content it hashes. Groups and tiers go
+    /// through this too, since their media ends up in token metadata.
+    pub(crate) fn validate_media(media: &Option<String>, media_hash: &Option<Base64VecU8>) -> Result<(), FansError> {
+        if media.is_some() != media_hash.is_some() {
+            return Err(FansError::InvalidMetadata("media and media_hash must be provided together".to_string()));
+        }
+        if media_hash.as_ref().is_some_and(|hash| hash.0.len() != 32) {
+            return Err(FansError::InvalidMetadata("media_hash must be 32 bytes".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Checks agent-supplied metadata before anything is scheduled, so a bad request fails
+    /// in `nft_mint` rather than in the callback after the devbot round-trip
+    fn validate_token_metadata(&self, metadata: &TokenMetadata) -> Result<(), FansError> {
+        Self::validate_media(&metadata.media, &metadata.media_hash)?;
+        if metadata.reference.is_some() != metadata.reference_hash.is_some() {
+            return Err(FansError::InvalidMetadata("reference and reference_hash must be provided together".to_string()));
+        }
+        if metadata.reference_hash.as_ref().is_some_and(|hash| hash.0.len() != 32) {
+            return Err(FansError::InvalidMetadata("reference_hash must be 32 bytes".to_string()));
+        }
+        let limits = &self.metadata_limits;
+        let fields = [
+            ("title", &metadata.title, limits.max_title_len),
+            ("description", &metadata.description, limits.max_description_len),
+            ("media", &metadata.media, limits.max_media_len),
+            ("reference", &metadata.reference, limits.max_reference_len),
+            ("extra", &metadata.extra, limits.max_extra_len),
+        ];
+        for (name, value, max_len) in fields {
+            if value.as_ref().is_some_and(|v| v.len() > max_len as usize) {
+                return Err(FansError::InvalidMetadata(format!("{} exceeds {} bytes", name, max_len)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Typed extra for a token. Tokens minted before the typed record existed fall back to
+    /// a lenient read of the metadata `extra`, then to the contract's default group.
+    fn internal_token_extra(&self, token: &Token) -> FanTokenExtra {
+        if let Some(extra) = self.token_extra.get(&token.token_id) {
+            return extra;
+        }
+        let metadata = token.metadata.as_ref();
+        let group_id = metadata
+            .and_then(|m| m.extra.as_ref())
+            .and_then(|e| serde_json::from_str::<serde_json::Value>(e).ok())
+            .and_then(|v| v.get("group_id").and_then(|g| g.as_str()).map(str::to_string))
+            .unwrap_or_else(Self::default_group_id);
+        let held_since = metadata
+            .and_then(|m| m.issued_at.as_ref())
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(0);
+        FanTokenExtra {
+            group_id,
+            tier: None,
+            held_since: U64(held_since),
+            flags: 0,
+            mint_price: U128(0),
+        }
+    }
+
+    /// Fills the metadata `extra` from the typed record so NEP-177 readers still see the group.
+    /// Keys of the agent's JSON object come along where they do not clash with the record, and
+    /// anything else it stored is served under `raw`.
+    fn internal_with_extra(&self, mut token: Token) -> Token {
+        if let Some(extra) = self.token_extra.get(&token.token_id) {
+            if let Some(metadata) = token.metadata.as_mut() {
+                let mut served = serde_json::to_value(&extra).unwrap();
+                let fields = served.as_object_mut().unwrap();
+                match metadata.extra.as_deref().map(serde_json::from_str::<serde_json::Value>) {
+                    Some(Ok(serde_json::Value::Object(agent_fields))) => {
+                        for (key, value) in agent_fields {
+                            fields.entry(key).or_insert(value);
+                        }
+                    }
+                    Some(_) => {
+                        fields.insert("raw".to_string(), metadata.extra.clone().into());
+                    }
+                    None => {}
+                }
+                metadata.extra = Some(served.to_string());
+            }
+        }
+        token
+    }
+
+    /// Applies the token's transfer policy, by default a one-year lock after mint. Suspended
+    /// members and tokens waiting to be burned cannot move at all.
+    fn assert_transferable(&self, token: &Token) {
+        if self.is_suspended(&token.owner_id) {
+            FansError::Suspended.panic();
+        }
+        if self.pending_burns.get(&token.token_id).is_some() {
+            FansError::BurnPending.panic();
+        }
+        let mint_timestamp = token
+            .metadata
+            .as_ref()
+            .and_then(|m| m.issued_at.as_ref())
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(0);
+        match self.internal_transfer_policy(&token.token_id) {
+            TransferPolicy::Anytime => {}
+            TransferPolicy::Never => FansError::NotTransferable.panic(),
+            TransferPolicy::AfterLockup => {
+                if env::block_timestamp() - mint_timestamp < 31_536_000_000_000_000 {
+                    FansError::Locked.panic();
+                }
+            }
+        }
+    }
+
+    /// Resets `held_since` once a token has changed hands and moves the membership on every
+    /// backend, and in the registry, from the previous owner to the new one
+    fn internal_on_transfer(&mut self, token_id: &TokenId, previous_owner_id: &AccountId) {
+        if let Some(token) = self.tokens.nft_token(token_id.clone()) {
+            let mut extra = self.internal_token_extra(&token);
+            extra.held_since = U64(env::block_timestamp());
+            self.token_extra.insert(token_id, &extra);
+            self.internal_sync_membership(&extra.group_id, previous_owner_id, SyncAction::Revoke);
+            self.internal_sync_membership(&extra.group_id, &token.owner_id, SyncAction::Add);
+            self.internal_notify_registry(previous_owner_id, false);
+            self.internal_notify_registry(&token.owner_id, true);
+        }
     }
 }
 
@@ -374,22 +1101,19 @@ impl NonFungibleTokenCore for Contract {
         approval_id: Option<u64>,
         memo: Option<String>,
     ) {
+        if self.paused {
+            FansError::Paused.panic();
+        }
         if self.owns_token(receiver_id.clone()) {
-            env::panic_str("Receiver already owns a token");
+            FansError::AlreadyOwns.panic();
         }
+        self.internal_assert_not_banned(&receiver_id).unwrap_or_else(|err| err.panic());
         let token = self.tokens.nft_token(token_id.clone()).unwrap_or_else(|| {
-            env::panic_str("Token not found");
+            FansError::TokenNotFound.panic();
         });
-        let mint_timestamp = token
-            .metadata
-            .as_ref()
-            .and_then(|m| m.issued_at.as_ref())
-            .and_then(|s| s.parse::<u64>().ok())
-            .unwrap_or(0);
-        if env::block_timestamp() - mint_timestamp < 31_536_000_000_000_000 {
-            env::panic_str("Transfer not allowed until one year after mint");
-        }
-        self.tokens.nft_transfer(receiver_id, token_id, approval_id, memo);
+        self.assert_transferable(&token);
+        self.tokens.nft_transfer(receiver_id, token_id.clone(), approval_id, memo);
+        self.internal_on_transfer(&token_id, &token.owner_id);
     }
 
     #[payable]
@@ -401,26 +1125,22 @@ impl NonFungibleTokenCore for Contract {
         memo: Option<String>,
         msg: String,
     ) -> PromiseOrValue<bool> {
+        if self.paused {
+            FansError::Paused.panic();
+        }
         if self.owns_token(receiver_id.clone()) {
-            env::panic_str("Receiver already owns a token");
+            FansError::AlreadyOwns.panic();
         }
+        self.internal_assert_not_banned(&receiver_id).unwrap_or_else(|err| err.panic());
         let token = self.tokens.nft_token(token_id.clone()).unwrap_or_else(|| {
-            env::panic_str("Token not found");
+            FansError::TokenNotFound.panic();
         });
-        let mint_timestamp = token
-            .metadata
-            .as_ref()
-            .and_then(|m| m.issued_at.as_ref())
-            .and_then(|s| s.parse::<u64>().ok())
-            .unwrap_or(0);
-        if env::block_timestamp() - mint_timestamp < 31_536_000_000_000_000 {
-            env::panic_str("Transfer not allowed until one year after mint");
-        }
+        self.assert_transferable(&token);
         self.tokens.nft_transfer_call(receiver_id, token_id, approval_id, memo, msg)
     }
 
     fn nft_token(&self, token_id: TokenId) -> Option<Token> {
-        self.tokens.nft_token(token_id)
+        self.tokens.nft_token(token_id).map(|token| self.internal_with_extra(token))
     }
 }
 
@@ -434,12 +1154,16 @@ impl NonFungibleTokenResolver for Contract {
         token_id: TokenId,
         approved_account_ids: Option<std::collections::HashMap<AccountId, u64>>,
     ) -> bool {
-        self.tokens.nft_resolve_transfer(
-            previous_owner_id,
+        let transferred = self.tokens.nft_resolve_transfer(
+            previous_owner_id.clone(),
             receiver_id,
-            token_id,
+            token_id.clone(),
             approved_account_ids
-        )
+        );
+        if transferred {
+            self.internal_on_transfer(&token_id, &previous_owner_id);
+        }
+        transferred
     }
 }
 
@@ -452,6 +1176,12 @@ impl NonFungibleTokenApproval for Contract {
         account_id: AccountId,
         msg: Option<String>,
     ) -> Option<Promise> {
+        if self.is_suspended(&env::predecessor_account_id()) {
+            FansError::Suspended.panic();
+        }
+        if env::attached_deposit().is_zero() {
+            return self.internal_approve(token_id, account_id, msg);
+        }
         self.tokens.nft_approve(token_id, account_id, msg)
     }
 
@@ -473,187 +1203,1657 @@ impl NonFungibleTokenApproval for Contract {
     ) -> bool {
         self.tokens.nft_is_approved(token_id, approved_account_id, approval_id)
     }
-}
+}
+
+#[near]
+impl NonFungibleTokenEnumeration for Contract {
+    fn nft_total_supply(&self) -> U128 {
+        self.tokens.nft_total_supply()
+    }
+
+    fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Token> {
+        self.tokens
+            .nft_tokens(from_index, limit)
+            .into_iter()
+            .map(|token| self.internal_with_extra(token))
+            .collect()
+    }
+
+    fn nft_supply_for_owner(&self, account_id: AccountId) -> U128 {
+        self.tokens.nft_supply_for_owner(account_id)
+    }
+
+    fn nft_tokens_for_owner(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Token> {
+        self.tokens
+            .nft_tokens_for_owner(account_id, from_index, limit)
+            .into_iter()
+            .map(|token| self.internal_with_extra(token))
+            .collect()
+    }
+}
+
+#[near]
+impl NonFungibleTokenMetadataProvider for Contract {
+    fn nft_metadata(&self) -> NFTContractMetadata {
+        self.metadata.get().unwrap()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::json_types::Base64VecU8;
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, NearToken};
+
+    const ONE_YOCTONEAR: NearToken = NearToken::from_yoctonear(1);
+    const MINT_STORAGE_COST: NearToken = NearToken::from_millinear(11); // 0.011 NEAR
+    const BURN_STORAGE_COST: NearToken = NearToken::from_millinear(1); // 0.001 NEAR
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id("theosis.1000fans.near".parse().unwrap())
+            .signer_account_id(predecessor_account_id.clone())
+            .predecessor_account_id(predecessor_account_id)
+            .account_balance(NearToken::from_near(100));
+        builder
+    }
+
+    fn sample_token_metadata() -> TokenMetadata {
+        TokenMetadata {
+            title: Some("1000fans Access Token".into()),
+            description: Some("Grants access to theosis.1000fans.near".into()),
+            media: None,
+            media_hash: None,
+            copies: Some(1u64),
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: Some(serde_json::to_string(&serde_json::json!({ "group_id": "theosis" })).unwrap()),
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        testing_env!(context.is_view(true).build());
+        assert_eq!(contract.nft_token("fan000".to_string()), None);
+        assert_eq!(contract.minted_count, 0);
+        assert!(contract.burned_ids.is_empty());
+        assert_eq!(contract.nft_total_supply(), U128::from(0));
+        assert_eq!(contract.nft_metadata().name, "1000fans");
+        assert_eq!(contract.get_owner().to_string(), "theosis.1000fans.near");
+    }
+
+    #[test]
+    fn test_new_club() {
+        let mut context = get_context("factory.near".parse().unwrap());
+        testing_env!(context.build());
+        let mut contract = Contract::new_club(
+            "theosis.devbot.near".parse().unwrap(),
+            "Theosis".to_string(),
+            "THEO".to_string(),
+            1,
+            accounts(0),
+            Some("factory.near".parse().unwrap()),
+        );
+        assert_eq!(contract.nft_metadata().name, "Theosis");
+        assert_eq!(contract.get_owner(), accounts(0));
+        assert_eq!(contract.get_max_supply(), 1);
+        assert_eq!(contract.factory_id, Some("factory.near".parse().unwrap()));
+
+        // fan000 uses up the whole supply
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        contract.initialize(None).unwrap();
+        assert_eq!(contract.get_group("theosis".to_string()).unwrap().group.max_supply, 1);
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).predecessor_account_id(accounts(0)).build());
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::SupplyExhausted);
+
+        // Upgrades come from the owner or the factory only
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        assert!(contract.upgrade().is_ok());
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert!(receipts.last().unwrap().actions.iter().any(|action| matches!(
+            action,
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, .. } if method_name == b"migrate"
+        )));
+        testing_env!(context.predecessor_account_id("factory.near".parse().unwrap()).build());
+        assert!(contract.upgrade().is_ok());
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert!(matches!(contract.upgrade().err().unwrap(), FansError::Unauthorized(_)));
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let agent_id: AccountId = "1000fans.near".parse().unwrap();
+        let metadata = NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.to_string(),
+            name: "1000fans".to_string(),
+            symbol: "1000F".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        };
+        let mut old = ContractV0 {
+            tokens: NonFungibleToken::new(
+                StorageKey::NonFungibleToken,
+                accounts(0),
+                Some(StorageKey::TokenMetadata),
+                Some(StorageKey::Enumeration),
+                Some(StorageKey::Approval),
+            ),
+            metadata: LazyOption::new(StorageKey::Metadata, Some(&metadata)),
+            minted_count: 2,
+            burned_ids: UnorderedSet::new(StorageKey::BurnedIds),
+            devbot_contract: LazyOption::new(StorageKey::DevbotContract, Some(&"theosis.devbot.near".parse().unwrap())),
+            _authorized_agents: LookupMap::new(StorageKey::AuthorizedAgents),
+            initialized: true,
+        };
+        old._authorized_agents.insert(&agent_id, &true);
+        old.tokens.internal_mint_with_refund("fan000".to_string(), agent_id.clone(), Some(sample_token_metadata()), None);
+        old.tokens.internal_mint_with_refund("fan002".to_string(), accounts(2), Some(sample_token_metadata()), None);
+        old.burned_ids.insert(&"fan001".to_string());
+        env::state_write(&old);
+
+        let mut contract = Contract::migrate();
+        assert_eq!(contract.nft_token("fan002".to_string()).unwrap().owner_id, accounts(2));
+        assert_eq!(contract.get_owner(), accounts(0));
+        assert_eq!(contract.nft_metadata().name, "1000fans");
+        assert_eq!(contract.next_token_number, 3);
+        assert!(contract.get_group("theosis".to_string()).is_some());
+        assert!(contract.owns_token(accounts(2)));
+
+        // Migrating state already in the new layout keeps it
+        env::state_write(&contract);
+        assert_eq!(Contract::migrate().minted_count, 2);
+
+        // The old agent mints without limits and moves to the new map
+        assert!(contract.is_authorized_agent(agent_id.clone()));
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).predecessor_account_id(agent_id.clone()).build());
+        assert!(contract.nft_mint(accounts(3), sample_token_metadata(), "theosis".to_string(), None, None).is_ok());
+        assert!(!contract.legacy_agents.contains_key(&agent_id));
+        assert_eq!(contract.get_agent(agent_id).unwrap().total_minted, 1);
+    }
+
+    #[test]
+    fn test_initialize() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        let token = contract.initialize(None).unwrap();
+        testing_env!(context.is_view(true).build());
+        assert_eq!(token.token_id, "fan000");
+        assert_eq!(token.owner_id.to_string(), "1000fans.near");
+        assert_eq!(contract.minted_count, 1);
+        assert_eq!(contract.nft_total_supply(), U128::from(1));
+        // Without limits the platform agent still gets a daily cap
+        let agent = contract.get_agent("1000fans.near".parse().unwrap()).unwrap();
+        assert_eq!(agent.limits, AgentLimits::platform_default());
+        assert_eq!(agent.daily_remaining, Some(100));
+    }
+
+    #[test]
+    #[should_panic(expected = "The contract is not initialized")]
+    fn test_default() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let _contract = Contract::default();
+    }
+
+    #[test]
+    fn test_mint() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+
+        // Verify initial state
+        let token = contract.nft_token("fan000".to_string()).unwrap();
+        assert_eq!(token.owner_id.to_string(), "1000fans.near");
+        assert_eq!(contract.minted_count, 1);
+        assert_eq!(contract.nft_total_supply(), U128::from(1));
+
+        // Call nft_mint
+        testing_env!(context
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
+            .build());
+        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
+
+        // Mock nft_mint_callback
+        testing_env!(context
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
+            .build());
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
+        assert_eq!(token.token_id, "fan001");
+        assert_eq!(token.owner_id, accounts(1));
+        assert!(contract.owns_token(accounts(1)));
+        assert_eq!(contract.minted_count, 2);
+        assert_eq!(contract.nft_total_supply(), U128::from(2));
+        let metadata = token.metadata.unwrap();
+        assert!(metadata.issued_at.is_some());
+        let extra: serde_json::Value = serde_json::from_str(&metadata.extra.unwrap()).unwrap();
+        assert_eq!(extra["group_id"], "theosis");
+
+        // Fail: account already owns token
+        testing_env!(context
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
+            .build());
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::AlreadyOwns);
+
+        // Fail: unauthorized caller
+        testing_env!(context
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(3))
+            .build());
+        let result = contract.nft_mint(accounts(3), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(
+            result.err().unwrap(),
+            FansError::Unauthorized("Only contract owner or authorized agents can mint".to_string())
+        );
+
+        // Fail: contract not initialized
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        testing_env!(context
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
+            .build());
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::NotInitialized);
+    }
+
+    #[test]
+    fn test_mint_invalid_metadata() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        testing_env!(context
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
+            .build());
+
+        // Fail: media without its hash
+        let mut metadata = sample_token_metadata();
+        metadata.media = Some("https://example.com/cover.png".to_string());
+        let result = contract.nft_mint(accounts(1), metadata.clone(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::InvalidMetadata("media and media_hash must be provided together".to_string()));
+
+        // Fail: hash of the wrong length
+        metadata.media_hash = Some(vec![0u8; 31].into());
+        let result = contract.nft_mint(accounts(1), metadata.clone(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::InvalidMetadata("media_hash must be 32 bytes".to_string()));
+
+        // Fail: title over the configured limit
+        let mut metadata = sample_token_metadata();
+        metadata.title = Some("x".repeat(65));
+        let result = contract.nft_mint(accounts(1), metadata.clone(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::InvalidMetadata("title exceeds 64 bytes".to_string()));
+
+        // Owner raises the limit
+        contract.set_metadata_limits(MetadataLimits { max_title_len: 128, ..MetadataLimits::default() }).unwrap();
+        assert_eq!(contract.get_metadata_limits().max_title_len, 128);
+        assert!(contract.nft_mint(accounts(1), metadata.clone(), "theosis".to_string(), None, None).is_ok());
+
+        // copies is always 1, whatever the agent sent
+        metadata.copies = Some(10);
+        let token = contract.nft_mint_callback(accounts(1), metadata, "theosis".to_string(), None, None, None, accounts(1));
+        assert_eq!(token.metadata.unwrap().copies, Some(1));
+
+        // Fail: only the owner can change limits
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.set_metadata_limits(MetadataLimits::default()).unwrap();
+        }));
+        assert!(result.is_err(), "Only the owner should set metadata limits");
+    }
+
+    #[test]
+    fn test_agent_limits() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(Some(AgentLimits { max_daily_mints: Some(1), ..Default::default() })).unwrap();
+        let agent = contract.get_agent("1000fans.near".parse().unwrap()).unwrap();
+        assert_eq!(agent.daily_remaining, Some(1));
+        assert_eq!(agent.total_remaining, None);
+
+        // Daily quota resets the next day
+        testing_env!(context.predecessor_account_id("1000fans.near".parse().unwrap()).build());
+        assert!(contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None).is_ok());
+        let result = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::QuotaExceeded);
+        testing_env!(context.block_timestamp(86_400_000_000_000).build());
+        assert!(contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None).is_ok());
+        let agent = contract.get_agent("1000fans.near".parse().unwrap()).unwrap();
+        assert_eq!(agent.total_minted, 2);
+        assert_eq!(agent.daily_remaining, Some(0));
+
+        // Total quota and expiry
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let limits = AgentLimits {
+            expires_at: Some(U64(2 * 86_400_000_000_000)),
+            max_total_mints: Some(1),
+            max_daily_mints: None,
+        };
+        contract.add_authorized_agent(accounts(4), Some(limits.clone())).unwrap();
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        assert!(contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None).is_ok());
+        let result = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::QuotaExceeded);
+        assert_eq!(contract.get_agent(accounts(4)).unwrap().total_remaining, Some(0));
+
+        // New limits keep what the agent already minted
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.add_authorized_agent(accounts(4), Some(AgentLimits { max_total_mints: Some(2), ..limits })).unwrap();
+        let agent = contract.get_agent(accounts(4)).unwrap();
+        assert_eq!(agent.total_minted, 1);
+        assert_eq!(agent.total_remaining, Some(1));
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        testing_env!(context.block_timestamp(2 * 86_400_000_000_000).build());
+        assert!(!contract.is_authorized_agent(accounts(4)));
+        let result = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::Unauthorized("Agent authorization has expired".to_string()));
+
+        // Revocation
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.remove_authorized_agent("1000fans.near".parse().unwrap()).unwrap();
+        assert!(contract.get_agent("1000fans.near".parse().unwrap()).is_none());
+        testing_env!(context.predecessor_account_id("1000fans.near".parse().unwrap()).build());
+        let result = contract.nft_mint(accounts(3), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(
+            result.err().unwrap(),
+            FansError::Unauthorized("Only contract owner or authorized agents can mint".to_string())
+        );
+    }
+
+    #[test]
+    fn test_devbot_settings() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        assert_eq!(contract.get_devbot_config(), DevbotConfig::default());
+        assert_eq!(contract.get_devbot_config().mint_callback_gas, Gas::from_tgas(10));
+
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        let config = DevbotConfig {
+            groups_contains_key_gas: Gas::from_tgas(20),
+            set_group_member_tier: CallBudget { gas: Gas::from_tgas(20), deposit: NearToken::from_millinear(5) },
+            ..DevbotConfig::default()
+        };
+        contract.set_devbot_config(config.clone()).unwrap();
+        assert_eq!(contract.get_devbot_config(), config);
+
+        // The new devbot takes over the old one's membership backend, budgets included
+        let mut backend = MembershipBackend::devbot("theosis.devbot.near".parse().unwrap());
+        backend.add.deposit = NearToken::from_millinear(5);
+        contract.add_membership_backend(backend).unwrap();
+        let live = Group { max_supply: 10, ..contract.get_group("theosis".to_string()).unwrap().group };
+        contract.create_group("live".to_string(), live).unwrap();
+        testing_env!(context.build());
+        contract.set_devbot_contract("theosis.devbot2.near".parse().unwrap()).unwrap();
+        assert_eq!(contract.get_devbot_contract().to_string(), "theosis.devbot2.near");
+        // Both groups are registered, on the new devbot only
+        let registered: Vec<_> = near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.actions.iter().any(|action| matches!(
+                action,
+                near_sdk::mock::MockAction::FunctionCallWeight { method_name, .. } if method_name == b"register_group"
+            )))
+            .map(|receipt| receipt.receiver_id.to_string())
+            .collect();
+        assert_eq!(registered, vec!["theosis.devbot2.near", "theosis.devbot2.near"]);
+        let backends = contract.get_membership_backends();
+        assert_eq!(backends.len(), 1);
+        assert_eq!(backends[0].contract_id.to_string(), "theosis.devbot2.near");
+        assert_eq!(backends[0].add.deposit, NearToken::from_millinear(5));
+
+        // Fail: only the owner can change either
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert!(contract.set_devbot_config(DevbotConfig::default()).is_err());
+        assert!(contract.set_devbot_contract(accounts(1)).is_err());
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+
+        // Fail: only the owner can pause
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert_eq!(
+            contract.pause().err().unwrap(),
+            FansError::Unauthorized("Only owner can pause the contract".to_string())
+        );
+
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.pause().unwrap();
+        assert!(contract.is_paused());
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::Paused);
+
+        contract.unpause().unwrap();
+        assert!(!contract.is_paused());
+        assert!(contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None).is_ok());
+    }
+
+    #[test]
+    fn test_events() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        let logs = get_logs();
+        assert_eq!(
+            logs[0],
+            r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"agent_added","data":{"agent_id":"1000fans.near"}}"#
+        );
+        assert_eq!(
+            logs[1],
+            r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_mint","data":[{"owner_id":"1000fans.near","token_ids":["fan000"]}]}"#
+        );
+
+        testing_env!(context.build());
+        contract.add_authorized_agent(accounts(1), None).unwrap();
+        contract.set_metadata_limits(MetadataLimits::default()).unwrap();
+        contract.pause().unwrap();
+        contract.unpause().unwrap();
+        assert_eq!(
+            get_logs(),
+            vec![
+                r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"agent_added","data":{"agent_id":"bob"}}"#,
+                r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"config_updated","data":{"field":"metadata_limits","updated_by":"theosis.1000fans.near"}}"#,
+                r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"paused","data":{"account_id":"theosis.1000fans.near"}}"#,
+                r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"unpaused","data":{"account_id":"theosis.1000fans.near"}}"#,
+            ]
+        );
+
+        // Backend outcomes are reported per call
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.on_membership_synced("theosis.devbot.near".parse().unwrap(), "theosis".to_string(), accounts(1), SyncAction::Revoke);
+        assert_eq!(
+            get_logs().last().unwrap(),
+            r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"group_sync_failed","data":{"backend_id":"theosis.devbot.near","group_id":"theosis","account_id":"bob","action":"revoke"}}"#
+        );
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.on_membership_synced("theosis.devbot.near".parse().unwrap(), "theosis".to_string(), accounts(1), SyncAction::Add);
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"group_sync_succeeded","data":{"backend_id":"theosis.devbot.near","group_id":"theosis","account_id":"bob","action":"add"}}"#]
+        );
+    }
+
+    #[test]
+    fn test_membership_backends() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        assert_eq!(contract.get_membership_backends(), vec![MembershipBackend::devbot("theosis.devbot.near".parse().unwrap())]);
+
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        let relay = MembershipBackend {
+            contract_id: "relay.near".parse().unwrap(),
+            register_method: None,
+            add_method: "grant".to_string(),
+            revoke_method: "revoke".to_string(),
+            register: CallBudget { gas: Gas::from_tgas(0), deposit: NearToken::from_yoctonear(0) },
+            add: CallBudget { gas: Gas::from_tgas(15), deposit: NearToken::from_yoctonear(0) },
+            revoke: CallBudget { gas: Gas::from_tgas(15), deposit: NearToken::from_yoctonear(0) },
+        };
+        contract.add_membership_backend(relay.clone()).unwrap();
+        assert_eq!(contract.get_membership_backends().len(), 2);
+        // Each backend's add plus its callback
+        assert_eq!(contract.internal_sync_gas(SyncAction::Add), Gas::from_tgas(50 + 5 + 15 + 5));
+
+        // Outcomes are kept per backend
+        for (backend_id, result) in [
+            ("theosis.devbot.near", PromiseResult::Successful(vec![])),
+            ("relay.near", PromiseResult::Failed),
+        ] {
+            testing_env!(
+                context.build(),
+                near_sdk::test_vm_config(),
+                near_sdk::RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![result]
+            );
+            contract.on_membership_synced(backend_id.parse().unwrap(), "theosis".to_string(), accounts(1), SyncAction::Add);
+        }
+        let sync = contract.get_membership_sync(accounts(1));
+        assert_eq!(sync.len(), 2);
+        assert_eq!(sync[0].backend_id.to_string(), "theosis.devbot.near");
+        assert!(sync[0].status.as_ref().unwrap().success);
+        assert_eq!(sync[1].backend_id.to_string(), "relay.near");
+        assert!(!sync[1].status.as_ref().unwrap().success);
+        assert_eq!(sync[1].status.as_ref().unwrap().action, SyncAction::Add);
+
+        testing_env!(context.build());
+        contract.remove_membership_backend("relay.near".parse().unwrap()).unwrap();
+        assert_eq!(contract.get_membership_backends().len(), 1);
+
+        // Fail: only the owner manages backends
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert!(contract.add_membership_backend(relay).is_err());
+        assert!(contract.remove_membership_backend("theosis.devbot.near".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(FansError::AlreadyOwns.code(), "ALREADY_OWNS");
+        assert_eq!(FansError::AlreadyOwns.to_string(), "ALREADY_OWNS: Account already owns a token");
+        assert_eq!(
+            FansError::InsufficientDeposit { attached: U128(1), required: U128(2) }.to_string(),
+            "INSUFFICIENT_DEPOSIT: Insufficient deposit: attached 1, required 2"
+        );
+        assert_eq!(FansError::UnknownGroup("theosis".to_string()).to_string(), "UNKNOWN_GROUP: Group theosis does not exist");
+    }
+
+    #[test]
+    fn test_mint_token_limit() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        testing_env!(context
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
+            .build());
+        assert_eq!(contract.get_owner().to_string(), "theosis.1000fans.near");
+        contract.minted_count = 1000;
+        let result = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::SupplyExhausted);
+    }
+
+    #[test]
+    fn test_groups() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        assert_eq!(contract.get_groups(None, None).len(), 1);
+        assert_eq!(contract.group_supply("theosis".to_string()), U128(1));
+
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        let live = Group {
+            title: "Theosis Live".to_string(),
+            description: None,
+            media: None,
+            media_hash: None,
+            max_supply: 1,
+            price: U128(NearToken::from_near(1).as_yoctonear()),
+        };
+        let result = contract.create_group("live".to_string(), Group { media: Some("https://example.com/live.png".to_string()), ..live.clone() });
+        assert_eq!(result.err().unwrap(), FansError::InvalidMetadata("media and media_hash must be provided together".to_string()));
+        contract.create_group("live".to_string(), live.clone()).unwrap();
+        assert_eq!(contract.create_group("live".to_string(), live.clone()).err().unwrap(), FansError::GroupExists("live".to_string()));
+        let view = contract.get_group("live".to_string()).unwrap();
+        assert_eq!(view.group, live);
+        assert_eq!(view.supply, U128(0));
+        assert_eq!(contract.get_groups(Some(U128(1)), Some(10))[0].group_id, "live");
+
+        // Fail: unknown group, or a deposit that does not cover the group price
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "unknown".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::UnknownGroup("unknown".to_string()));
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "live".to_string(), None, None);
+        assert!(matches!(result.err().unwrap(), FansError::InsufficientDeposit { .. }));
+
+        // The price stays with the contract, only storage is refunded
+        let deposit = MINT_STORAGE_COST.saturating_add(NearToken::from_near(1));
+        testing_env!(context.attached_deposit(deposit).build());
+        assert!(contract.nft_mint(accounts(1), sample_token_metadata(), "live".to_string(), None, None).is_ok());
+        testing_env!(context.attached_deposit(deposit).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "live".to_string(), None, None, None, accounts(1));
+        assert_eq!(contract.get_token_extra(token.token_id.clone()).unwrap().group_id, "live");
+        assert_eq!(contract.group_supply("live".to_string()), U128(1));
+        let tokens = contract.nft_tokens_for_group("live".to_string(), None, None);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].owner_id, accounts(1));
+
+        // Fail: the group is at its cap
+        testing_env!(context.attached_deposit(deposit).build());
+        let result = contract.nft_mint(accounts(2), sample_token_metadata(), "live".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::GroupFull("live".to_string()));
+
+        // Burning frees the slot
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).predecessor_account_id(accounts(1)).build());
+        contract.nft_burn(token.token_id).unwrap();
+        testing_env!(context.block_timestamp(env::block_timestamp() + DEFAULT_BURN_GRACE_PERIOD).build());
+        assert_eq!(contract.finalize_burns(None), 1);
+        assert_eq!(contract.group_supply("live".to_string()), U128(0));
+        assert!(contract.nft_tokens_for_group("live".to_string(), None, None).is_empty());
+
+        // Fail: only the owner manages groups
+        assert!(contract.create_group("vip".to_string(), live.clone()).is_err());
+        assert!(contract.update_group("live".to_string(), live).is_err());
+    }
+
+    #[test]
+    fn test_tiers() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        let gold = Tier {
+            title: "Gold".to_string(),
+            media: Some("https://example.com/gold.png".to_string()),
+            media_hash: Some(vec![1u8; 32].into()),
+            max_supply: 1,
+            price: U128(NearToken::from_near(2).as_yoctonear()),
+            transfer_policy: TransferPolicy::Never,
+        };
+        // Fail: media without its hash would mint metadata NEP-177 rejects
+        let result = contract.set_tier("gold".to_string(), Tier { media_hash: None, ..gold.clone() });
+        assert_eq!(result.err().unwrap(), FansError::InvalidMetadata("media and media_hash must be provided together".to_string()));
+        contract.set_tier("gold".to_string(), gold.clone()).unwrap();
+        assert_eq!(contract.get_tiers().len(), 1);
+        assert_eq!(contract.get_tier_supply("gold".to_string()), U128(0));
+
+        // Fail: unknown tier, or a deposit that does not cover the tier price
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), Some("platinum".to_string()), None);
+        assert_eq!(result.err().unwrap(), FansError::UnknownTier("platinum".to_string()));
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), Some("gold".to_string()), None);
+        assert!(matches!(result.err().unwrap(), FansError::InsufficientDeposit { .. }));
+
+        let deposit = MINT_STORAGE_COST.saturating_add(NearToken::from_near(2));
+        testing_env!(context.attached_deposit(deposit).build());
+        assert!(contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), Some("gold".to_string()), None).is_ok());
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), Some("gold".to_string()), None, None, accounts(1));
+        assert_eq!(contract.get_tier_supply("gold".to_string()), U128(1));
+        assert_eq!(contract.get_tier(token.token_id.clone()).unwrap().tier, gold);
+        assert_eq!(contract.get_tier("fan000".to_string()), None);
+        let metadata = token.metadata.clone().unwrap();
+        assert_eq!(metadata.media, gold.media);
+        assert_eq!(metadata.media_hash, gold.media_hash);
+        let extra: serde_json::Value = serde_json::from_str(&metadata.extra.unwrap()).unwrap();
+        assert_eq!(extra["tier"], "gold");
+
+        // Fail: the tier is at its cap
+        let result = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string(), Some("gold".to_string()), None);
+        assert_eq!(result.err().unwrap(), FansError::TierFull("gold".to_string()));
+
+        // Fail: gold tokens never move, even after the lockup
+        testing_env!(context
+            .attached_deposit(ONE_YOCTONEAR)
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(env::block_timestamp() + 31_536_000_000_000_000)
+            .build());
+        let transfer_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_transfer(accounts(2), token.token_id.clone(), None, None);
+        }));
+        assert!(transfer_result.is_err(), "Gold tokens should not be transferable");
+
+        // Burning frees the slot
+        contract.nft_burn(token.token_id).unwrap();
+        testing_env!(context.block_timestamp(env::block_timestamp() + DEFAULT_BURN_GRACE_PERIOD).build());
+        assert_eq!(contract.finalize_burns(None), 1);
+        assert_eq!(contract.get_tier_supply("gold".to_string()), U128(0));
+
+        // Fail: only the owner sets tiers
+        assert!(contract.set_tier("gold".to_string(), gold).is_err());
+    }
+
+    #[test]
+    fn test_upgrade_tier() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        let tier = |title: &str, near: u128| Tier {
+            title: title.to_string(),
+            media: Some(format!("https://example.com/{}.png", title)),
+            media_hash: Some(env::sha256(title.as_bytes()).into()),
+            max_supply: 1,
+            price: U128(NearToken::from_near(near).as_yoctonear()),
+            transfer_policy: TransferPolicy::AfterLockup,
+        };
+        contract.set_tier("silver".to_string(), tier("silver", 1)).unwrap();
+        contract.set_tier("gold".to_string(), tier("gold", 3)).unwrap();
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST.saturating_add(NearToken::from_near(1))).build());
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), Some("silver".to_string()), None, None, accounts(1));
+        let held_since = contract.get_token_extra(token.token_id.clone()).unwrap().held_since;
+
+        // Fail: not the holder, not higher, or not paid for
+        testing_env!(context.attached_deposit(NearToken::from_near(3)).block_timestamp(1_000).build());
+        let result = contract.upgrade_tier(token.token_id.clone(), "gold".to_string());
+        assert!(matches!(result.err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let result = contract.upgrade_tier(token.token_id.clone(), "silver".to_string());
+        assert_eq!(result.err().unwrap(), FansError::NotAnUpgrade);
+        testing_env!(context.attached_deposit(NearToken::from_near(1)).build());
+        let result = contract.upgrade_tier(token.token_id.clone(), "gold".to_string());
+        assert!(matches!(result.err().unwrap(), FansError::InsufficientDeposit { .. }));
+
+        // The difference is charged, the number and tenure are kept
+        testing_env!(context.attached_deposit(NearToken::from_near(3)).build());
+        let upgraded = contract.upgrade_tier(token.token_id.clone(), "gold".to_string()).unwrap();
+        assert_eq!(upgraded.token_id, token.token_id);
+        let metadata = upgraded.metadata.unwrap();
+        assert_eq!(metadata.media, Some("https://example.com/gold.png".to_string()));
+        assert_eq!(metadata.media_hash, Some(env::sha256(b"gold").into()));
+        let extra = contract.get_token_extra(token.token_id.clone()).unwrap();
+        assert_eq!(extra.tier, Some("gold".to_string()));
+        assert_eq!(extra.held_since, held_since);
+        assert_eq!(extra.mint_price, U128(NearToken::from_near(3).as_yoctonear()));
+        assert_eq!(contract.get_tier_supply("silver".to_string()), U128(0));
+        assert_eq!(contract.get_tier_supply("gold".to_string()), U128(1));
+        assert_eq!(
+            get_logs().last().unwrap(),
+            &format!(
+                r#"EVENT_JSON:{{"standard":"1000fans","version":"1.0.0","event":"tier_upgraded","data":{{"token_id":"{}","owner_id":"bob","old_tier":"silver","new_tier":"gold"}}}}"#,
+                token.token_id
+            )
+        );
+
+        // Fail: going back down
+        let result = contract.upgrade_tier(token.token_id.clone(), "silver".to_string());
+        assert_eq!(result.err().unwrap(), FansError::NotAnUpgrade);
+
+        // Fail: the target tier is full
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let token = contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(2));
+        testing_env!(context.attached_deposit(NearToken::from_near(3)).predecessor_account_id(accounts(2)).build());
+        let result = contract.upgrade_tier(token.token_id, "gold".to_string());
+        assert_eq!(result.err().unwrap(), FansError::TierFull("gold".to_string()));
+    }
+
+    #[test]
+    fn test_claim_with_voucher() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let public_key =
+            PublicKey::from_parts(near_sdk::CurveType::ED25519, signing_key.verifying_key().to_bytes().to_vec()).unwrap();
+        let voucher = Voucher {
+            contract_id: "theosis.1000fans.near".parse().unwrap(),
+            agent_id: "1000fans.near".parse().unwrap(),
+            account_id: accounts(1),
+            group_id: "theosis".to_string(),
+            tier: None,
+            expires_at: U64(1_000),
+            nonce: U64(1),
+        };
+        let sign = |voucher: &Voucher| {
+            use ed25519_dalek::Signer;
+            Base64VecU8(signing_key.sign(&borsh::to_vec(voucher).unwrap()).to_bytes().to_vec())
+        };
+
+        // Only agents register keys, and a voucher needs one
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let result = contract.set_voucher_key(public_key.clone());
+        assert!(matches!(result.err().unwrap(), FansError::Unauthorized(_)));
+        let result = contract.claim_with_voucher(voucher.clone(), sign(&voucher));
+        assert_eq!(result.err().unwrap(), FansError::InvalidVoucher("agent has no voucher key".to_string()));
+        testing_env!(context.predecessor_account_id("1000fans.near".parse().unwrap()).build());
+        contract.set_voucher_key(public_key.clone()).unwrap();
+        assert_eq!(contract.get_agent("1000fans.near".parse().unwrap()).unwrap().voucher_key, Some(public_key));
+
+        // Fail: someone else's voucher, a tampered voucher, an expired voucher
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let result = contract.claim_with_voucher(voucher.clone(), sign(&voucher));
+        assert_eq!(result.err().unwrap(), FansError::InvalidVoucher("voucher is for another account".to_string()));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let tampered = Voucher { nonce: U64(2), ..voucher.clone() };
+        let result = contract.claim_with_voucher(tampered, sign(&voucher));
+        assert_eq!(result.err().unwrap(), FansError::InvalidVoucher("bad signature".to_string()));
+        testing_env!(context.block_timestamp(1_000).build());
+        let result = contract.claim_with_voucher(voucher.clone(), sign(&voucher));
+        assert_eq!(result.err().unwrap(), FansError::InvalidVoucher("voucher has expired".to_string()));
+
+        // A claim counts against the agent and burns the nonce
+        testing_env!(context.block_timestamp(0).build());
+        assert!(!contract.is_voucher_used("1000fans.near".parse().unwrap(), U64(1)));
+        assert!(contract.claim_with_voucher(voucher.clone(), sign(&voucher)).is_ok());
+        assert!(contract.is_voucher_used("1000fans.near".parse().unwrap(), U64(1)));
+        assert_eq!(contract.get_agent("1000fans.near".parse().unwrap()).unwrap().total_minted, 1);
+        let result = contract.claim_with_voucher(voucher.clone(), sign(&voucher));
+        assert_eq!(result.err().unwrap(), FansError::VoucherUsed);
+
+        // A mint that fails in its callback gives the nonce back
+        let nonce = Some(("1000fans.near".parse().unwrap(), U64(1)));
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert_eq!(contract.on_mint_resolved(accounts(1), U128(MINT_STORAGE_COST.as_yoctonear()), U128(0), nonce, None), None);
+        assert!(!contract.is_voucher_used("1000fans.near".parse().unwrap(), U64(1)));
+    }
+
+    #[test]
+    fn test_gifts() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
+        let gift_key: PublicKey = "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap();
+        let cost = NearToken::from_yoctonear(contract.get_gift_cost("theosis".to_string()).0);
+
+        // Fail: not a member, or not paid for
+        testing_env!(context.attached_deposit(cost).predecessor_account_id(accounts(2)).build());
+        let result = contract.create_gift(gift_key.clone(), "theosis".to_string());
+        assert!(matches!(result.err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).predecessor_account_id(accounts(1)).build());
+        let result = contract.create_gift(gift_key.clone(), "theosis".to_string());
+        assert!(matches!(result.err().unwrap(), FansError::InsufficientDeposit { .. }));
+
+        // The gift holds a slot in its group
+        testing_env!(context.attached_deposit(cost).build());
+        contract.create_gift(gift_key.clone(), "theosis".to_string()).unwrap();
+        let gift = contract.get_gift(gift_key.clone()).unwrap();
+        assert_eq!(gift.giver_id, accounts(1));
+        assert_eq!(gift.deposit, U128(cost.as_yoctonear()));
+        assert_eq!(contract.create_gift(gift_key.clone(), "theosis".to_string()).err().unwrap(), FansError::GiftExists);
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let group = Group { max_supply: 3, ..contract.groups.get(&"theosis".to_string()).unwrap() };
+        contract.update_group("theosis".to_string(), group).unwrap();
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let result = contract.nft_mint(accounts(3), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::GroupFull("theosis".to_string()));
+
+        // Fail: claimed by anything but the gift key, or too late
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).predecessor_account_id(accounts(2)).build());
+        assert!(matches!(contract.claim_gift(accounts(2)).err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context
+            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
+            .signer_account_pk(gift_key.clone())
+            .block_timestamp(gift.expires_at.0)
+            .build());
+        assert_eq!(contract.claim_gift(accounts(2)).err().unwrap(), FansError::GiftExpired);
+        testing_env!(context.block_timestamp(0).build());
+        assert_eq!(contract.claim_gift(accounts(1)).err().unwrap(), FansError::AlreadyOwns);
+
+        // A failed mint puts the gift back, a successful one settles it
+        assert!(contract.claim_gift(accounts(2)).is_ok());
+        assert!(contract.get_gift(gift_key.clone()).is_none());
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.on_gift_claimed(gift_key.clone(), gift.clone(), accounts(2), U128(0));
+        assert_eq!(contract.get_gift(gift_key.clone()), Some(gift.clone()));
+        testing_env!(context.build());
+        contract.claim_gift(accounts(2)).unwrap();
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let token = contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(2));
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(serde_json::to_vec(&token).unwrap())]
+        );
+        contract.on_gift_claimed(gift_key.clone(), gift, accounts(2), U128(0));
+        assert_eq!(
+            get_logs().last().unwrap(),
+            &format!(
+                r#"EVENT_JSON:{{"standard":"1000fans","version":"1.0.0","event":"gift_claimed","data":{{"giver_id":"bob","owner_id":"charlie","token_id":"{}"}}}}"#,
+                token.token_id
+            )
+        );
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).build());
+        assert_eq!(contract.claim_gift(accounts(3)).err().unwrap(), FansError::UnknownGift);
+
+        // Refunds only after expiry, and only to the giver
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let group = Group { max_supply: 1000, ..contract.groups.get(&"theosis".to_string()).unwrap() };
+        contract.update_group("theosis".to_string(), group).unwrap();
+        testing_env!(context.attached_deposit(cost).predecessor_account_id(accounts(1)).build());
+        contract.create_gift(gift_key.clone(), "theosis".to_string()).unwrap();
+        assert_eq!(contract.refund_gift(gift_key.clone()).err().unwrap(), FansError::GiftNotExpired);
+        let expires_at = contract.get_gift(gift_key.clone()).unwrap().expires_at.0;
+        testing_env!(context.block_timestamp(expires_at).predecessor_account_id(accounts(2)).build());
+        assert!(matches!(contract.refund_gift(gift_key.clone()).err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.refund_gift(gift_key.clone()).unwrap();
+        assert!(contract.get_gift(gift_key).is_none());
+    }
 
-#[near]
-impl NonFungibleTokenEnumeration for Contract {
-    fn nft_total_supply(&self) -> U128 {
-        self.tokens.nft_total_supply()
-    }
+    #[test]
+    fn test_sponsored_calls() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
+
+        // The owner funds the balance and hands out calls
+        testing_env!(context.attached_deposit(NearToken::from_near(1)).predecessor_account_id(accounts(1)).build());
+        assert!(matches!(contract.fund_sponsorship().err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.fund_sponsorship().unwrap();
+        assert_eq!(contract.get_sponsor_balance(), U128(NearToken::from_near(1).as_yoctonear()));
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        contract.set_sponsored_calls(accounts(1), 2).unwrap();
+        contract.set_sponsored_calls(accounts(3), 1).unwrap();
+
+        // A fan that signs its own approval pays for it
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).predecessor_account_id(accounts(1)).signer_account_id(accounts(1)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| contract.nft_approve(token.token_id.clone(), accounts(2), None)));
+        assert!(result.is_err());
+        assert_eq!(contract.get_sponsored_calls(accounts(1)), 2);
+
+        // A relayed approval with nothing attached is paid by the sponsor
+        testing_env!(context
+            .attached_deposit(NearToken::from_yoctonear(0))
+            .predecessor_account_id(accounts(1))
+            .signer_account_id("relayer.near".parse().unwrap())
+            .build());
+        assert!(contract.nft_approve(token.token_id.clone(), accounts(2), None).is_none());
+        assert!(contract.nft_is_approved(token.token_id, accounts(2), Some(1)));
+        assert_eq!(contract.get_sponsored_calls(accounts(1)), 1);
+        let approval_cost = (accounts(2).as_str().len() as u128 + 12) * env::storage_byte_cost().as_yoctonear();
+        let balance = NearToken::from_near(1).as_yoctonear() - approval_cost;
+        assert_eq!(contract.get_sponsor_balance(), U128(balance));
+
+        // An agent's mint for a fan with calls left has its storage paid; the calls are the fan's,
+        // so a mint for another fan is not
+        testing_env!(context.predecessor_account_id("1000fans.near".parse().unwrap()).build());
+        assert!(contract.nft_mint(accounts(3), sample_token_metadata(), "theosis".to_string(), None, None).is_ok());
+        assert_eq!(contract.get_sponsor_balance(), U128(balance - MINT_STORAGE_COST.as_yoctonear()));
+        assert_eq!(contract.get_sponsored_calls(accounts(3)), 0);
+        assert_eq!(contract.get_sponsored_calls("1000fans.near".parse().unwrap()), 0);
+        let result = contract.nft_mint(accounts(4), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert!(matches!(result.err().unwrap(), FansError::InsufficientDeposit { .. }));
+
+        // If that mint fails in its callback, the sponsor gets the storage back
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let sponsored = U128(MINT_STORAGE_COST.as_yoctonear());
+        assert_eq!(contract.on_mint_resolved("1000fans.near".parse().unwrap(), U128(0), sponsored, None, None), None);
+        assert_eq!(contract.get_sponsor_balance(), U128(balance));
 
-    fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Token> {
-        self.tokens.nft_tokens(from_index, limit)
+        // Withdrawals are capped by the balance
+        testing_env!(context
+            .attached_deposit(ONE_YOCTONEAR)
+            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
+            .signer_account_id("theosis.1000fans.near".parse().unwrap())
+            .build());
+        let result = contract.withdraw_sponsorship(U128(NearToken::from_near(1).as_yoctonear()));
+        assert!(matches!(result.err().unwrap(), FansError::SponsorBalanceTooLow { .. }));
+        contract.withdraw_sponsorship(contract.get_sponsor_balance()).unwrap();
+        assert_eq!(contract.get_sponsor_balance(), U128(0));
     }
 
-    fn nft_supply_for_owner(&self, account_id: AccountId) -> U128 {
-        self.tokens.nft_supply_for_owner(account_id)
-    }
+    #[test]
+    fn test_mint_request_id() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        let request_id = Some("onramp-session-1".to_string());
+
+        testing_env!(context.predecessor_account_id("1000fans.near".parse().unwrap()).build());
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, request_id.clone());
+        assert!(matches!(result, Ok(PromiseOrValue::Promise(_))));
+        assert!(contract.get_mint_by_request("onramp-session-1".to_string()).is_none());
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, request_id.clone(), None, accounts(1));
+        assert_eq!(contract.get_mint_by_request("onramp-session-1".to_string()), Some(token.clone()));
+
+        // A retry that raced the first mint into the callback fails there, and is settled with
+        // the same token: what the agent attached goes back to it rather than the relayer that
+        // signed, what the sponsor covered goes back to the sponsor, and the token's payer stays
+        testing_env!(context.signer_account_id("relayer.near".parse().unwrap()).attached_deposit(MINT_STORAGE_COST).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, request_id.clone(), None, "1000fans.near".parse().unwrap())
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.minted_count, 2);
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let (attached, sponsored) = (U128(1), U128(MINT_STORAGE_COST.as_yoctonear() - 1));
+        let again = contract.on_mint_resolved("1000fans.near".parse().unwrap(), attached, sponsored, None, request_id.clone());
+        assert_eq!(again, Some(token.clone()));
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts.last().unwrap().receiver_id, "1000fans.near".parse::<AccountId>().unwrap());
+        assert_eq!(contract.get_sponsor_balance(), sponsored);
+        assert_eq!(contract.storage_payers.get(&token.token_id), Some(StoragePayer::Account(accounts(1))));
+
+        // A later retry is answered right away, without touching the agent's quota
+        let total_minted = contract.get_agent("1000fans.near".parse().unwrap()).unwrap().total_minted;
+        match contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, request_id.clone()) {
+            Ok(PromiseOrValue::Value(again)) => assert_eq!(again, Some(token.clone())),
+            _ => panic!("expected the minted token"),
+        }
+        assert_eq!(contract.get_agent("1000fans.near".parse().unwrap()).unwrap().total_minted, total_minted);
+        assert!(contract.get_mint_by_request("onramp-session-2".to_string()).is_none());
 
-    fn nft_tokens_for_owner(
-        &self,
-        account_id: AccountId,
-        from_index: Option<U128>,
-        limit: Option<u64>,
-    ) -> Vec<Token> {
-        self.tokens.nft_tokens_for_owner(account_id, from_index, limit)
+        // Once the token is burned and its ID minted to another fan, the request no longer
+        // resolves to it
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.nft_burn(token.token_id.clone()).unwrap();
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let reminted = contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(2));
+        assert_eq!(reminted.token_id, token.token_id);
+        assert_eq!(contract.get_mint_by_request("onramp-session-1".to_string()), None);
+        testing_env!(context.predecessor_account_id("1000fans.near".parse().unwrap()).build());
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, request_id);
+        assert_eq!(result.err().unwrap(), FansError::TokenNotFound);
     }
-}
 
-#[near]
-impl NonFungibleTokenMetadataProvider for Contract {
-    fn nft_metadata(&self) -> NFTContractMetadata {
-        self.metadata.get().unwrap()
+    #[test]
+    fn test_suspensions() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
+        let after_lockup = 31_536_000_000_000_000;
+
+        // Only the owner appoints moderators, and only moderators suspend
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).predecessor_account_id(accounts(2)).build());
+        assert!(matches!(contract.add_moderator(accounts(2)).err().unwrap(), FansError::Unauthorized(_)));
+        let result = contract.suspend_member(accounts(1), U64(2 * after_lockup), "spam".to_string());
+        assert!(matches!(result.err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.add_moderator(accounts(3)).unwrap();
+        assert_eq!(contract.get_moderators(), vec![accounts(3)]);
+
+        // Fail: not a member, or a suspension already over
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let result = contract.suspend_member(accounts(4), U64(2 * after_lockup), "spam".to_string());
+        assert_eq!(result.err().unwrap(), FansError::TokenNotFound);
+        let result = contract.suspend_member(accounts(1), U64(0), "spam".to_string());
+        assert!(matches!(result.err().unwrap(), FansError::InvalidSuspension(_)));
+
+        contract.suspend_member(accounts(1), U64(2 * after_lockup), "spam".to_string()).unwrap();
+        assert!(!contract.is_active_member(accounts(1)));
+        let result = contract.suspend_member(accounts(1), U64(3 * after_lockup), "spam".to_string());
+        assert!(matches!(result.err().unwrap(), FansError::InvalidSuspension(_)));
+
+        // The token cannot move or be approved, and only a moderator can lift it early
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(after_lockup).build());
+        let transfer_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_transfer(accounts(2), token.token_id.clone(), None, None);
+        }));
+        assert!(transfer_result.is_err());
+        let approve_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_approve(token.token_id.clone(), accounts(2), None);
+        }));
+        assert!(approve_result.is_err());
+        assert!(matches!(contract.reinstate_member(accounts(1)).err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.reinstate_member(accounts(1)).unwrap();
+        assert!(contract.is_active_member(accounts(1)));
+        assert_eq!(contract.reinstate_member(accounts(1)).err().unwrap(), FansError::NotSuspended);
+
+        // Once a suspension runs out the member can reinstate themselves, which is what restores
+        // their group access; until then they are not active
+        contract.suspend_member(accounts(1), U64(after_lockup + 1_000), "spam again".to_string()).unwrap();
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(after_lockup + 1_000).build());
+        assert!(!contract.is_active_member(accounts(1)));
+        contract.reinstate_member(accounts(1)).unwrap();
+        assert!(contract.is_active_member(accounts(1)));
+        let history = contract.get_suspensions(accounts(1));
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].reinstated_at, Some(U64(after_lockup)));
+        assert_eq!(history[1].reason, "spam again");
+        assert_eq!(
+            get_logs().last().unwrap(),
+            r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"member_reinstated","data":{"account_id":"bob","reinstated_by":"bob"}}"#
+        );
+        contract.nft_transfer(accounts(2), token.token_id, None, None);
+        assert!(contract.is_active_member(accounts(2)));
     }
-}
-
-#[cfg(all(test, not(target_arch = "wasm32")))]
-mod tests {
-    use super::*;
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::{testing_env, NearToken};
 
-    const ONE_YOCTONEAR: NearToken = NearToken::from_yoctonear(1);
-    const MINT_STORAGE_COST: NearToken = NearToken::from_millinear(7); // 0.007 NEAR
-    const BURN_STORAGE_COST: NearToken = NearToken::from_millinear(1); // 0.001 NEAR
+    #[test]
+    fn test_bans() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
+        let transferable = contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(2));
+
+        // Only the owner bans, and never themselves
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).predecessor_account_id(accounts(2)).build());
+        let result = contract.burn_and_ban(token.token_id.clone(), "abuse".to_string(), None);
+        assert!(matches!(result.err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let result = contract.ban_account("theosis.1000fans.near".parse().unwrap(), "oops".to_string(), None);
+        assert!(matches!(result.err().unwrap(), FansError::InvalidBan(_)));
+        let result = contract.burn_and_ban(token.token_id.clone(), "abuse".to_string(), Some(U64(0)));
+        assert!(matches!(result.err().unwrap(), FansError::InvalidBan(_)));
+        assert!(contract.owns_token(accounts(1)));
 
-    fn get_context(predecessor_account_id: AccountId) -> VMContextBuilder {
-        let mut builder = VMContextBuilder::new();
-        builder
-            .current_account_id("theosis.1000fans.near".parse().unwrap())
-            .signer_account_id(predecessor_account_id.clone())
-            .predecessor_account_id(predecessor_account_id)
-            .account_balance(NearToken::from_near(100));
-        builder
-    }
+        contract.burn_and_ban(token.token_id.clone(), "abuse".to_string(), None).unwrap();
+        assert!(!contract.owns_token(accounts(1)));
+        assert!(contract.is_banned(accounts(1)));
+        assert_eq!(
+            get_logs().last().unwrap(),
+            r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"account_banned","data":{"account_id":"bob","banned_by":"theosis.1000fans.near","until":null,"reason":"abuse"}}"#
+        );
 
-    fn sample_token_metadata() -> TokenMetadata {
-        TokenMetadata {
-            title: Some("1000fans Access Token".into()),
-            description: Some("Grants access to theosis.1000fans.near".into()),
-            media: None,
-            media_hash: None,
-            copies: Some(1u64),
-            issued_at: None,
-            expires_at: None,
-            starts_at: None,
-            updated_at: None,
-            extra: Some(serde_json::to_string(&serde_json::json!({ "group_id": "theosis" })).unwrap()),
-            reference: None,
-            reference_hash: None,
+        // A banned account cannot be minted to again
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(result.err().unwrap(), FansError::Banned(accounts(1)));
+
+        // The callback checks again, a ban or a token may have come after the request
+        for owner_id in [accounts(1), accounts(2)] {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.nft_mint_callback(owner_id.clone(), sample_token_metadata(), "theosis".to_string(), None, None, None, owner_id.clone())
+            }));
+            assert!(result.is_err());
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.nft_commit_mint(owner_id.clone(), sample_token_metadata(), "theosis".to_string(), None, None, owner_id.clone())
+            }));
+            assert!(result.is_err());
         }
-    }
+        assert_eq!(contract.minted_count, 2);
 
-    #[test]
-    fn test_new() {
-        let mut context = get_context(accounts(0));
-        testing_env!(context.build());
-        let contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        testing_env!(context.is_view(true).build());
-        assert_eq!(contract.nft_token("fan000".to_string()), None);
-        assert_eq!(contract.minted_count, 0);
-        assert!(contract.burned_ids.is_empty());
-        assert_eq!(contract.nft_total_supply(), U128::from(0));
-        assert_eq!(contract.nft_metadata().name, "1000fans");
-        assert_eq!(contract.get_owner().to_string(), "theosis.1000fans.near");
+        // Nor receive a transfer
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        contract.ban_account(accounts(3), "spam".to_string(), Some(U64(1_000))).unwrap();
+        testing_env!(context.predecessor_account_id(accounts(2)).block_timestamp(31_536_000_000_000_000).build());
+        let transfer_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_transfer(accounts(1), transferable.token_id.clone(), None, None);
+        }));
+        assert!(transfer_result.is_err());
+
+        // Bans list page by page, and a timed ban runs out on its own
+        assert_eq!(contract.get_ban_count(), 2);
+        let page = contract.get_bans(Some(U128(1)), Some(1));
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].account_id, accounts(3));
+        assert_eq!(page[0].ban.until, Some(U64(1_000)));
+        assert!(!contract.is_banned(accounts(3)));
+        contract.nft_transfer(accounts(3), transferable.token_id, None, None);
+        assert!(contract.owns_token(accounts(3)));
+
+        // Lifting a ban lets the account back in
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.unban_account(accounts(1)).unwrap();
+        assert!(!contract.is_banned(accounts(1)));
+        assert_eq!(contract.unban_account(accounts(1)).err().unwrap(), FansError::NotBanned);
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        assert!(contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None).is_ok());
     }
 
     #[test]
-    fn test_initialize() {
-        let mut context = get_context(accounts(0));
+    fn test_swaps() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
         testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        let token = contract.initialize();
-        testing_env!(context.is_view(true).build());
-        assert_eq!(token.token_id, "fan000");
-        assert_eq!(token.owner_id.to_string(), "1000fans.near");
-        assert_eq!(contract.minted_count, 1);
-        assert_eq!(contract.nft_total_supply(), U128::from(1));
+        contract.initialize(None).unwrap();
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
+        let lucky = contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(2));
+        let after_lockup = 31_536_000_000_000_000;
+        let swap_deposit = NearToken::from_millinear(10);
+
+        // Fail: offering someone else's token, swapping with yourself, or not covering storage
+        testing_env!(context.attached_deposit(swap_deposit).predecessor_account_id(accounts(1)).build());
+        let result = contract.propose_swap(lucky.token_id.clone(), token.token_id.clone());
+        assert!(matches!(result.err().unwrap(), FansError::Unauthorized(_)));
+        let result = contract.propose_swap(token.token_id.clone(), token.token_id.clone());
+        assert!(matches!(result.err().unwrap(), FansError::InvalidSwap(_)));
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        let result = contract.propose_swap(token.token_id.clone(), lucky.token_id.clone());
+        assert!(matches!(result.err().unwrap(), FansError::InsufficientDeposit { .. }));
+        assert_eq!(contract.get_swap(U64(0)), None);
+
+        testing_env!(context.attached_deposit(swap_deposit).build());
+        let swap_id = contract.propose_swap(token.token_id.clone(), lucky.token_id.clone()).unwrap();
+        let swap = contract.get_swap(swap_id).unwrap();
+        assert_eq!(swap.counterparty_id, accounts(2));
+        assert!(swap.deposit.0 > 0);
+
+        // Only the holder of the requested token accepts, and only once both tokens may move
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        assert!(matches!(contract.accept_swap(swap_id).err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let accept_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.accept_swap(swap_id).unwrap();
+        }));
+        assert!(accept_result.is_err(), "Tokens are still locked");
+        testing_env!(context.block_timestamp(after_lockup).attached_deposit(NearToken::from_yoctonear(0)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| contract.accept_swap(swap_id)));
+        assert!(result.is_err(), "1 yoctoNEAR is required");
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        contract.accept_swap(swap_id).unwrap();
+        assert_eq!(
+            get_logs().last().unwrap(),
+            r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"swap_accepted","data":{"swap_id":"0","proposer_id":"bob","counterparty_id":"charlie"}}"#
+        );
+        assert_eq!(contract.nft_token(token.token_id.clone()).unwrap().owner_id, accounts(2));
+        assert_eq!(contract.nft_token(lucky.token_id.clone()).unwrap().owner_id, accounts(1));
+        assert_eq!(contract.get_token_extra(lucky.token_id.clone()).unwrap().held_since, U64(after_lockup));
+        assert_eq!(contract.get_swap(swap_id), None);
+        assert_eq!(contract.accept_swap(swap_id).err().unwrap(), FansError::UnknownSwap);
+
+        // Either party can call a swap off
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(NearToken::from_yoctonear(0)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.propose_swap(lucky.token_id.clone(), token.token_id.clone())
+        }));
+        assert!(result.is_err(), "A deposit is required");
+        testing_env!(context.attached_deposit(swap_deposit).build());
+        let swap_id = contract.propose_swap(lucky.token_id.clone(), token.token_id.clone()).unwrap();
+        assert_eq!(swap_id, U64(1));
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert!(matches!(contract.cancel_swap(swap_id).err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.cancel_swap(swap_id).unwrap();
+        assert_eq!(contract.cancel_swap(swap_id).err().unwrap(), FansError::UnknownSwap);
     }
 
     #[test]
-    #[should_panic(expected = "The contract is not initialized")]
-    fn test_default() {
-        let context = get_context(accounts(0));
-        testing_env!(context.build());
-        let _contract = Contract::default();
+    fn test_offers() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
+        let price = U128(NearToken::from_near(1).as_yoctonear());
+        let escrow = NearToken::from_millinear(1_010);
+
+        // Fail: holders cannot offer, and the deposit has to cover the price and the storage
+        testing_env!(context.attached_deposit(escrow).predecessor_account_id(accounts(1)).build());
+        assert_eq!(contract.make_offer(None, price, None).err().unwrap(), FansError::AlreadyOwns);
+        testing_env!(context.attached_deposit(NearToken::from_near(1)).predecessor_account_id(accounts(2)).build());
+        let result = contract.make_offer(Some(token.token_id.clone()), price, None);
+        assert!(matches!(result.err().unwrap(), FansError::InsufficientDeposit { .. }));
+        assert_eq!(contract.get_offer(U64(0)), None);
+
+        testing_env!(context.attached_deposit(escrow).build());
+        let offer_id = contract.make_offer(Some(token.token_id.clone()), price, None).unwrap();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let open_offer_id = contract.make_offer(None, price, Some(U64(1_000))).unwrap();
+        assert_eq!(contract.get_offers_for_token(token.token_id.clone(), None, None).len(), 2);
+        assert_eq!(contract.get_offers_for_token("fan000".to_string(), None, None)[0].offer_id, open_offer_id);
+        assert_eq!(contract.get_offers_for_token(token.token_id.clone(), Some(U128(1)), Some(1))[0].offer_id, open_offer_id);
+        assert!(contract.get_offers_by_account(accounts(4), None, None).is_empty());
+        let offers = contract.get_offers_by_account(accounts(2), None, None);
+        assert_eq!(offers.len(), 1);
+        assert!(offers[0].offer.deposit.0 > 0);
+
+        // Only the holder accepts, and only once the token is out of its lockup
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        assert!(matches!(contract.accept_offer(offer_id, token.token_id.clone()).err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| contract.accept_offer(offer_id, token.token_id.clone())));
+        assert!(result.is_err());
+        assert!(matches!(contract.accept_offer(offer_id, "fan000".to_string()).err().unwrap(), FansError::Unauthorized(_)));
+
+        // Expired offers cannot be accepted, and anyone can clear them
+        let after_lockup = 31_536_000_000_000_000;
+        testing_env!(context.block_timestamp(after_lockup).build());
+        assert_eq!(contract.accept_offer(open_offer_id, token.token_id.clone()).err().unwrap(), FansError::OfferExpired);
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.cancel_offer(open_offer_id).unwrap();
+        assert!(matches!(contract.cancel_offer(offer_id).err().unwrap(), FansError::Unauthorized(_)));
+
+        // Fail: the price is above the resale cap
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.set_resale_cap(Some(ResaleCap::Absolute(U128(1)))).unwrap();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let result = contract.accept_offer(offer_id, token.token_id.clone());
+        assert!(matches!(result.err().unwrap(), FansError::ResaleCapExceeded { .. }));
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.set_resale_cap(None).unwrap();
+
+        // Fail: no yoctoNEAR attached
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(NearToken::from_yoctonear(0)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| contract.accept_offer(offer_id, token.token_id.clone())));
+        assert!(result.is_err());
+
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        contract.accept_offer(offer_id, token.token_id.clone()).unwrap();
+        assert_eq!(contract.nft_token(token.token_id.clone()).unwrap().owner_id, accounts(2));
+        assert_eq!(contract.get_offer(offer_id), None);
+        assert!(contract.get_offers_for_token(token.token_id.clone(), None, None).is_empty());
+        assert!(contract.get_offers_by_account(accounts(2), None, None).is_empty());
+        assert_eq!(contract.get_token_extra(token.token_id.clone()).unwrap().held_since, U64(after_lockup));
+        assert_eq!(
+            get_logs().last().unwrap(),
+            &format!(r#"EVENT_JSON:{{"standard":"1000fans","version":"1.0.0","event":"offer_accepted","data":{{"offer_id":"0","token_id":"{}","seller_id":"bob","buyer_id":"charlie","price":"{}"}}}}"#, token.token_id, price.0)
+        );
+
+        // The buyer can withdraw their offer at any time
+        testing_env!(context.attached_deposit(escrow).predecessor_account_id(accounts(3)).build());
+        let offer_id = contract.make_offer(Some(token.token_id.clone()), price, None).unwrap();
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        contract.cancel_offer(offer_id).unwrap();
+        assert!(contract.get_offers_by_account(accounts(3), None, None).is_empty());
+
+        // Fail: an offer the resale cap already refuses, for any holder's token only if absolute
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.set_resale_cap(Some(ResaleCap::Absolute(U128(1)))).unwrap();
+        testing_env!(context.attached_deposit(escrow).predecessor_account_id(accounts(3)).build());
+        let result = contract.make_offer(Some(token.token_id.clone()), price, None);
+        assert!(matches!(result.err().unwrap(), FansError::ResaleCapExceeded { .. }));
+        let result = contract.make_offer(None, price, None);
+        assert!(matches!(result.err().unwrap(), FansError::ResaleCapExceeded { .. }));
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.set_resale_cap(None).unwrap();
+
+        // Burning the token cancels its offers and refunds the buyer
+        testing_env!(context.attached_deposit(escrow).predecessor_account_id(accounts(3)).build());
+        let offer_id = contract.make_offer(Some(token.token_id.clone()), price, None).unwrap();
+        let offer = contract.get_offer(offer_id).unwrap();
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).predecessor_account_id(accounts(2)).build());
+        contract.leave_club(token.token_id.clone()).unwrap();
+        assert_eq!(contract.get_offer(offer_id), None);
+        assert!(contract.get_offers_by_account(accounts(3), None, None).is_empty());
+        let refund = near_sdk::test_utils::get_created_receipts().into_iter().find(|receipt| receipt.receiver_id == accounts(3));
+        assert!(matches!(
+            refund.unwrap().actions[0],
+            near_sdk::mock::MockAction::Transfer { deposit, .. } if deposit.as_yoctonear() == price.0 + offer.deposit.0
+        ));
     }
 
     #[test]
-    fn test_mint() {
+    fn test_auctions() {
         let mut context = get_context("theosis.1000fans.near".parse().unwrap());
         testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        contract.initialize();
+        contract.initialize(None).unwrap();
+        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
+
+        // Only free fanNNN IDs within the supply can be reserved, and only by the owner
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        for token_id in ["fan001", "vip", "fan7", "fan1000"] {
+            let result = contract.reserve_ids(vec![token_id.to_string()]);
+            assert!(matches!(result.err().unwrap(), FansError::InvalidReservation(_)), "{}", token_id);
+        }
+        contract.reserve_ids(vec!["fan002".to_string(), "fan777".to_string()]).unwrap();
+        assert_eq!(contract.get_reserved_ids(None, None), vec!["fan002".to_string(), "fan777".to_string()]);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert!(matches!(contract.reserve_ids(vec!["fan003".to_string()]).err().unwrap(), FansError::Unauthorized(_)));
+
+        // Sequential minting skips reserved IDs
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let token = contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(2));
+        assert_eq!(token.token_id, "fan003");
+
+        // Fail: the ID is not reserved, or the starting price does not cover the mint
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        let start = U128(NearToken::from_near(1).as_yoctonear());
+        let result = contract.start_auction("fan004".to_string(), "theosis".to_string(), start, None, U64(1_000));
+        assert!(matches!(result.err().unwrap(), FansError::InvalidAuction(_)));
+        let result = contract.start_auction("fan777".to_string(), "theosis".to_string(), U128(1), None, U64(1_000));
+        assert!(matches!(result.err().unwrap(), FansError::InvalidAuction(_)));
+        let increment = Some(U128(NearToken::from_millinear(100).as_yoctonear()));
+        contract.start_auction("fan777".to_string(), "theosis".to_string(), start, increment, U64(1_000)).unwrap();
+        assert!(matches!(contract.release_ids(vec!["fan777".to_string()]).err().unwrap(), FansError::InvalidReservation(_)));
+
+        // Holders cannot bid, and bids have to clear the starting price and then the increment
+        testing_env!(context.attached_deposit(NearToken::from_near(2)).predecessor_account_id(accounts(1)).build());
+        assert_eq!(contract.place_bid("fan777".to_string()).err().unwrap(), FansError::AlreadyOwns);
+        testing_env!(context.attached_deposit(NearToken::from_millinear(500)).predecessor_account_id(accounts(3)).build());
+        assert_eq!(contract.place_bid("fan777".to_string()).err().unwrap(), FansError::BidTooLow { minimum: start });
+        testing_env!(context.attached_deposit(NearToken::from_near(1)).build());
+        contract.paused = true;
+        assert_eq!(contract.place_bid("fan777".to_string()).err().unwrap(), FansError::Paused);
+        contract.paused = false;
+        contract.place_bid("fan777".to_string()).unwrap();
+        testing_env!(context.attached_deposit(NearToken::from_millinear(1050)).predecessor_account_id(accounts(4)).build());
+        assert!(matches!(contract.place_bid("fan777".to_string()).err().unwrap(), FansError::BidTooLow { .. }));
+        testing_env!(context.attached_deposit(NearToken::from_near(2)).build());
+        contract.place_bid("fan777".to_string()).unwrap();
+        let auction = contract.get_auction("fan777".to_string()).unwrap();
+        assert_eq!(auction.highest_bidder, Some(accounts(4)));
+        assert_eq!(auction.highest_bid, U128(NearToken::from_near(2).as_yoctonear()));
+        assert_eq!(contract.settle_auction("fan777".to_string()).err().unwrap(), FansError::AuctionNotEnded);
+
+        // Once it has ended the winner is minted the reserved ID
+        testing_env!(context.attached_deposit(NearToken::from_near(3)).predecessor_account_id(accounts(3)).block_timestamp(1_000).build());
+        assert_eq!(contract.place_bid("fan777".to_string()).err().unwrap(), FansError::AuctionEnded);
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).build());
+        assert!(matches!(contract.settle_auction("fan777".to_string()).unwrap(), PromiseOrValue::Promise(_)));
+        assert_eq!(contract.get_auction("fan777".to_string()), None);
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let token = contract.nft_mint_callback(accounts(4), sample_token_metadata(), "theosis".to_string(), None, None, Some("fan777".to_string()), accounts(4));
+        assert_eq!(token.token_id, "fan777");
+        assert_eq!(contract.get_reserved_ids(None, None), vec!["fan002".to_string()]);
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(serde_json::to_vec(&token).unwrap())]
+        );
+        assert_eq!(contract.on_auction_settled("fan777".to_string(), auction).unwrap().owner_id, accounts(4));
+        assert_eq!(
+            get_logs().last().unwrap(),
+            r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"auction_settled","data":{"token_id":"fan777","winner_id":"eugene","amount":"2000000000000000000000000"}}"#
+        );
 
-        // Verify initial state
-        let token = contract.nft_token("fan000".to_string()).unwrap();
-        assert_eq!(token.owner_id.to_string(), "1000fans.near");
-        assert_eq!(contract.minted_count, 1);
-        assert_eq!(contract.nft_total_supply(), U128::from(1));
+        // An auction without bids closes without a sale, and released IDs are minted again
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).block_timestamp(2_000).build());
+        contract.start_auction("fan002".to_string(), "theosis".to_string(), start, None, U64(3_000)).unwrap();
+        assert_eq!(contract.settle_auction("fan002".to_string()).err().unwrap(), FansError::AuctionNotEnded);
+        contract.cancel_auction("fan002".to_string()).unwrap();
+        contract.release_ids(vec!["fan002".to_string()]).unwrap();
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let token = contract.nft_mint_callback(accounts(3), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(3));
+        assert_eq!(token.token_id, "fan002");
+        let token = contract.nft_mint_callback(accounts(5), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(5));
+        assert_eq!(token.token_id, "fan004");
+    }
 
-        // Call nft_mint
-        testing_env!(context
-            .attached_deposit(MINT_STORAGE_COST)
-            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
-            .build());
-        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string());
+    fn random_club(context: &mut VMContextBuilder) -> Contract {
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new_club(
+            "theosis.devbot.near".parse().unwrap(),
+            "1000fans".to_string(),
+            "1000F".to_string(),
+            8,
+            "theosis.1000fans.near".parse().unwrap(),
+            None,
+        );
+        contract.initialize(None).unwrap();
+        // fan001 is minted sequentially and fan003 reserved, leaving 2, 4, 5, 6 and 7 to draw
+        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        contract.reserve_ids(vec!["fan003".to_string()]).unwrap();
+        contract.set_random_assignment(true).unwrap();
+        contract
+    }
 
-        // Mock nft_mint_callback
-        testing_env!(context
-            .attached_deposit(MINT_STORAGE_COST)
-            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
-            .build());
-        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string());
-        assert_eq!(token.token_id, "fan001");
-        assert_eq!(token.owner_id, accounts(1));
-        assert!(contract.owns_token(accounts(1)));
-        assert_eq!(contract.minted_count, 2);
-        assert_eq!(contract.nft_total_supply(), U128::from(2));
-        let metadata = token.metadata.unwrap();
-        assert!(metadata.issued_at.is_some());
-        let extra: serde_json::Value = serde_json::from_str(&metadata.extra.unwrap()).unwrap();
-        assert_eq!(extra["group_id"], "theosis");
+    #[test]
+    fn test_random_assignment_is_uniform() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        let trials = 10_000;
+        let mut contract = random_club(&mut context);
+        let mut counts = std::collections::HashMap::new();
+        for trial in 0..trials {
+            // A fresh environment every hundred draws keeps within the prepaid gas
+            if trial % 100 == 0 {
+                testing_env!(context.build());
+            }
+            // Each draw goes back into the pool so every trial draws from the same IDs
+            let token_id = contract.internal_draw_token_id(env::sha256(&(trial as u32).to_le_bytes()));
+            contract.internal_return_to_draw_pool(&token_id);
+            *counts.entry(token_id).or_insert(0u32) += 1;
+        }
 
-        // Fail: account already owns token
-        testing_env!(context
-            .attached_deposit(MINT_STORAGE_COST)
-            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
-            .build());
-        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string());
-        assert_eq!(result.unwrap_err(), "Account already owns a token");
+        // Each free ID comes up a fifth of the time, within five standard deviations
+        let expected = trials as f64 / 5.0;
+        let tolerance = 5.0 * (trials as f64 * 0.2 * 0.8).sqrt();
+        assert_eq!(counts.len(), 5, "{:?}", counts);
+        for token_id in ["fan002", "fan004", "fan005", "fan006", "fan007"] {
+            let count = counts[token_id] as f64;
+            assert!((count - expected).abs() < tolerance, "{} drawn {} times", token_id, count);
+        }
+    }
 
-        // Fail: unauthorized caller
-        testing_env!(context
-            .attached_deposit(MINT_STORAGE_COST)
-            .predecessor_account_id(accounts(3))
-            .build());
-        let result = contract.nft_mint(accounts(3), sample_token_metadata(), "theosis".to_string());
-        assert_eq!(result.unwrap_err(), "Only contract owner or authorized agents can mint");
+    #[test]
+    fn test_random_assignment() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        let mut contract = random_club(&mut context);
+        assert!(contract.is_random_assignment());
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert!(matches!(contract.set_random_assignment(false).err().unwrap(), FansError::Unauthorized(_)));
+
+        // The commit holds a slot of the supply until the reveal
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).block_height(1).build());
+        let _ = contract.nft_commit_mint(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, accounts(2));
+        assert_eq!(contract.get_pending_draws(), 1);
+        assert_eq!(
+            get_logs().last().unwrap(),
+            r#"EVENT_JSON:{"standard":"1000fans","version":"1.0.0","event":"mint_committed","data":{"owner_id":"charlie","group_id":"theosis","committed_at":"1"}}"#
+        );
 
-        // Fail: contract not initialized
-        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        testing_env!(context
-            .attached_deposit(MINT_STORAGE_COST)
-            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
-            .build());
-        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string());
-        assert_eq!(result.unwrap_err(), "Contract not initialized");
+        // Fail: the reveal runs in the commit block
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_reveal_mint(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, accounts(2), U64(1))
+        }));
+        assert!(result.is_err());
+
+        // Every free ID is drawn once, and then the supply is exhausted
+        let mut drawn = Vec::new();
+        for (index, owner_id) in vec![accounts(2), accounts(3), accounts(4), accounts(5), "greg.near".parse().unwrap()].into_iter().enumerate() {
+            testing_env!(context.random_seed([index as u8 + 1; 32]).block_height(2).build());
+            drawn.push(contract.nft_reveal_mint(owner_id.clone(), sample_token_metadata(), "theosis".to_string(), None, None, owner_id, U64(1)).token_id);
+        }
+        assert_eq!(contract.get_pending_draws(), 0);
+        drawn.sort();
+        assert_eq!(drawn, vec!["fan002", "fan004", "fan005", "fan006", "fan007"]);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_commit_mint(accounts(0), sample_token_metadata(), "theosis".to_string(), None, None, accounts(0))
+        }));
+        assert!(result.is_err());
+
+        // A burned ID goes back into the draw
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        contract.nft_burn("fan005".to_string()).unwrap();
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).block_height(3).build());
+        let token = contract.nft_reveal_mint(accounts(0), sample_token_metadata(), "theosis".to_string(), None, None, accounts(0), U64(2));
+        assert_eq!(token.token_id, "fan005");
+
+        // A failed reveal frees its slot
+        contract.pending_draws = 1;
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert_eq!(contract.on_draw_revealed(), None);
+        assert_eq!(contract.get_pending_draws(), 0);
     }
 
     #[test]
-    fn test_mint_token_limit() {
+    fn test_resale_cap() {
         let mut context = get_context("theosis.1000fans.near".parse().unwrap());
         testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        contract.initialize();
-        testing_env!(context
-            .attached_deposit(MINT_STORAGE_COST)
-            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
-            .build());
-        assert_eq!(contract.get_owner().to_string(), "theosis.1000fans.near");
-        contract.minted_count = 1000;
-        let result = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string());
-        assert_eq!(result.unwrap_err(), "Cannot mint more than 1000 tokens");
+        contract.initialize(None).unwrap();
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        let gold = Tier {
+            title: "Gold".to_string(),
+            media: None,
+            media_hash: None,
+            max_supply: 10,
+            price: U128(NearToken::from_near(2).as_yoctonear()),
+            transfer_policy: TransferPolicy::Anytime,
+        };
+        contract.set_tier("gold".to_string(), gold).unwrap();
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST.saturating_add(NearToken::from_near(2))).build());
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), Some("gold".to_string()), None, None, accounts(1));
+        let token_id = token.token_id;
+        assert_eq!(contract.get_token_extra(token_id.clone()).unwrap().mint_price, U128(NearToken::from_near(2).as_yoctonear()));
+
+        // Uncapped, the whole balance goes to the holder
+        assert_eq!(contract.get_resale_cap(token_id.clone()), None);
+        let balance = U128(NearToken::from_near(10).as_yoctonear());
+        let payout = contract.nft_payout(token_id.clone(), balance, None).unwrap();
+        assert_eq!(payout.payout, std::collections::HashMap::from([(accounts(1), balance)]));
+        assert!(matches!(contract.nft_payout(token_id.clone(), balance, Some(0)).err().unwrap(), FansError::InvalidPayout(_)));
+
+        // A multiple of the mint price: 1.5x of 2 NEAR
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).predecessor_account_id(accounts(1)).build());
+        assert!(matches!(contract.set_resale_cap(Some(ResaleCap::MintPriceMultiple(15_000))).err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.set_resale_cap(Some(ResaleCap::MintPriceMultiple(15_000))).unwrap();
+        let cap = U128(NearToken::from_near(3).as_yoctonear());
+        assert_eq!(contract.get_resale_cap(token_id.clone()), Some(cap));
+        assert_eq!(contract.nft_payout(token_id.clone(), balance, None).err().unwrap(), FansError::ResaleCapExceeded { cap });
+        // fan000 was free, there is nothing to multiply
+        assert_eq!(contract.get_resale_cap("fan000".to_string()), None);
+        assert!(contract.nft_payout("fan000".to_string(), balance, None).is_ok());
+        // A multiple past u128 leaves the token uncapped
+        let extra = contract.token_extra.get(&token_id).unwrap();
+        contract.token_extra.insert(&token_id, &FanTokenExtra { mint_price: U128(u128::MAX / 2), ..extra.clone() });
+        assert_eq!(contract.get_resale_cap(token_id.clone()), None);
+        contract.token_extra.insert(&token_id, &extra);
+
+        // Fail: a marketplace settles above the cap, and the token stays put
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let result = contract.nft_transfer_payout(accounts(2), token_id.clone(), None, None, balance, None);
+        assert_eq!(result.err().unwrap(), FansError::ResaleCapExceeded { cap });
+        assert_eq!(contract.nft_token(token_id.clone()).unwrap().owner_id, accounts(1));
+        let payout = contract.nft_transfer_payout(accounts(2), token_id.clone(), None, None, cap, None).unwrap();
+        assert_eq!(payout.payout, std::collections::HashMap::from([(accounts(1), cap)]));
+        assert_eq!(contract.nft_token(token_id.clone()).unwrap().owner_id, accounts(2));
+
+        // An absolute cap is the same for every token
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.set_resale_cap(Some(ResaleCap::Absolute(U128(NearToken::from_near(1).as_yoctonear())))).unwrap();
+        assert_eq!(contract.get_resale_cap(token_id.clone()), Some(U128(NearToken::from_near(1).as_yoctonear())));
+        assert_eq!(contract.get_resale_cap("fan000".to_string()), Some(U128(NearToken::from_near(1).as_yoctonear())));
+        contract.set_resale_cap(None).unwrap();
+        assert_eq!(contract.get_resale_cap(token_id), None);
     }
 
     #[test]
@@ -661,19 +2861,19 @@ mod tests {
         let mut context = get_context("theosis.1000fans.near".parse().unwrap());
         testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        contract.initialize();
+        contract.initialize(None).unwrap();
 
         // Mint a token
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string());
+        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string());
+        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
         assert_eq!(contract.minted_count, 2);
 
         // Burn the token
@@ -681,7 +2881,9 @@ mod tests {
             .attached_deposit(BURN_STORAGE_COST)
             .predecessor_account_id(accounts(1))
             .build());
-        contract.nft_burn("fan001".to_string());
+        contract.nft_burn("fan001".to_string()).unwrap();
+        testing_env!(context.block_timestamp(env::block_timestamp() + DEFAULT_BURN_GRACE_PERIOD).build());
+        assert_eq!(contract.finalize_burns(None), 1);
         assert_eq!(contract.minted_count, 1);
         assert!(contract.burned_ids.contains(&"fan001".to_string()));
         assert!(!contract.owns_token(accounts(1)));
@@ -692,34 +2894,258 @@ mod tests {
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        let _ = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string());
+        let _ = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None);
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string());
+        contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(2));
         assert_eq!(contract.minted_count, 2);
         assert!(contract.burned_ids.is_empty());
     }
 
+    #[test]
+    fn test_pending_burns() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
+        let after_lockup = 31_536_000_000_000_000;
+
+        // A holder's burn waits, the token stays with them but cannot move
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).predecessor_account_id(accounts(1)).block_timestamp(after_lockup).build());
+        contract.nft_burn(token.token_id.clone()).unwrap();
+        assert_eq!(
+            get_logs().last().unwrap(),
+            &format!(
+                r#"EVENT_JSON:{{"standard":"1000fans","version":"1.0.0","event":"burn_requested","data":{{"owner_id":"bob","token_id":"fan001","final_after":"{}"}}}}"#,
+                after_lockup + DEFAULT_BURN_GRACE_PERIOD
+            )
+        );
+        assert!(contract.owns_token(accounts(1)));
+        assert_eq!(contract.get_pending_burns(None, None)[0].token_id, token.token_id);
+        assert_eq!(contract.nft_burn(token.token_id.clone()).err().unwrap(), FansError::BurnPending);
+        let transfer_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_transfer(accounts(2), token.token_id.clone(), None, None);
+        }));
+        assert!(transfer_result.is_err());
+
+        // Only the holder can take it back
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        assert!(matches!(contract.cancel_burn(token.token_id.clone()).err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.cancel_burn(token.token_id.clone()).unwrap();
+        assert_eq!(contract.get_pending_burn(token.token_id.clone()), None);
+        assert_eq!(contract.cancel_burn(token.token_id.clone()).err().unwrap(), FansError::NoPendingBurn);
+
+        // The slot is only recycled once the grace period is over and the burn is finalized
+        contract.nft_burn(token.token_id.clone()).unwrap();
+        assert_eq!(contract.finalize_burns(None), 0);
+        testing_env!(context.block_timestamp(after_lockup + DEFAULT_BURN_GRACE_PERIOD).build());
+        assert!(contract.burned_ids.is_empty());
+        assert_eq!(contract.finalize_burns(None), 1);
+        assert!(!contract.owns_token(accounts(1)));
+        assert!(contract.burned_ids.contains(&token.token_id));
+        assert!(contract.get_pending_burns(None, None).is_empty());
+
+        // Burns are finalized by deadline, not by when they were requested
+        let now = after_lockup + DEFAULT_BURN_GRACE_PERIOD;
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let slow = contract.nft_mint_callback(accounts(4), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(4));
+        let fast = contract.nft_mint_callback(accounts(5), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(5));
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).predecessor_account_id(accounts(4)).block_timestamp(2 * now).build());
+        contract.nft_burn(slow.token_id.clone()).unwrap();
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.set_burn_grace_period(U64(1_000)).unwrap();
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.nft_burn(fast.token_id.clone()).unwrap();
+        testing_env!(context.block_timestamp(2 * now + 1_000).build());
+        assert_eq!(contract.finalize_burns(Some(1)), 1);
+        assert!(!contract.owns_token(accounts(5)));
+        assert!(contract.owns_token(accounts(4)));
+        assert_eq!(contract.finalize_burns(None), 0);
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.cancel_burn(slow.token_id).unwrap();
+        assert!(contract.burn_queue.is_empty());
+        testing_env!(context.predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.set_burn_grace_period(U64(DEFAULT_BURN_GRACE_PERIOD)).unwrap();
+
+        // The owner's burns, and every burn once the grace period is 0, are immediate
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let token = contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(2));
+        let other = contract.nft_mint_callback(accounts(3), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(3));
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        contract.nft_burn(token.token_id).unwrap();
+        assert!(!contract.owns_token(accounts(2)));
+        contract.set_burn_grace_period(U64(0)).unwrap();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert!(contract.set_burn_grace_period(U64(1)).is_err());
+        contract.nft_burn(other.token_id).unwrap();
+        assert!(!contract.owns_token(accounts(3)));
+    }
+
+    #[test]
+    fn test_leave_club() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        let token = contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
+
+        // Only the holder can leave with their token, not even the owner
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).build());
+        assert!(matches!(contract.leave_club(token.token_id.clone()).err().unwrap(), FansError::Unauthorized(_)));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert_eq!(contract.leave_club("fan999".to_string()).err().unwrap(), FansError::TokenNotFound);
+
+        let refund = contract.leave_club(token.token_id.clone()).unwrap();
+        assert!(refund.0 > 0);
+        assert!(refund.0 < MINT_STORAGE_COST.as_yoctonear());
+        assert!(!contract.owns_token(accounts(1)));
+        assert!(contract.burned_ids.contains(&token.token_id));
+        assert_eq!(
+            get_logs().last().unwrap(),
+            &format!(
+                r#"EVENT_JSON:{{"standard":"1000fans","version":"1.0.0","event":"member_left","data":{{"account_id":"bob","token_id":"fan001","refund":"{}"}}}}"#,
+                refund.0
+            )
+        );
+
+        // Storage an agent paid goes back to the agent, not the holder
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let agent_paid = contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, None, "1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).predecessor_account_id(accounts(2)).build());
+        contract.leave_club(agent_paid.token_id).unwrap();
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts.last().unwrap().receiver_id, "1000fans.near".parse::<AccountId>().unwrap());
+
+        // Sponsored storage goes back to the sponsor balance
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        let sponsored = contract.nft_mint_callback(accounts(3), sample_token_metadata(), "theosis".to_string(), None, None, None, "1000fans.near".parse().unwrap());
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(serde_json::to_vec(&sponsored).unwrap())]
+        );
+        contract.on_mint_resolved("1000fans.near".parse().unwrap(), U128(0), U128(MINT_STORAGE_COST.as_yoctonear()), None, None);
+
+        // Fail: the contract is paused, or the holder is suspended
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+        contract.pause().unwrap();
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).predecessor_account_id(accounts(3)).build());
+        assert_eq!(contract.leave_club(sponsored.token_id.clone()).err().unwrap(), FansError::Paused);
+        testing_env!(context.attached_deposit(ONE_YOCTONEAR).predecessor_account_id("theosis.1000fans.near".parse().unwrap()).build());
+        contract.unpause().unwrap();
+        contract.suspend_member(accounts(3), U64(1_000), "spam".to_string()).unwrap();
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).predecessor_account_id(accounts(3)).build());
+        assert_eq!(contract.leave_club(sponsored.token_id.clone()).err().unwrap(), FansError::Suspended);
+
+        testing_env!(context.block_timestamp(1_000).build());
+        let refund = contract.leave_club(sponsored.token_id).unwrap();
+        assert_eq!(contract.get_sponsor_balance(), refund);
+    }
+
+    #[test]
+    fn test_token_extra() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+        let extra = contract.get_token_extra("fan000".to_string()).unwrap();
+        assert_eq!(extra.group_id, "theosis");
+
+        // Agent-supplied extra that is not even JSON does not affect the record, and is served raw
+        let mut metadata = sample_token_metadata();
+        metadata.extra = Some("not json".to_string());
+        testing_env!(context
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
+            .block_timestamp(1_000)
+            .build());
+        contract.nft_mint_callback(accounts(1), metadata, "theosis".to_string(), None, None, None, accounts(1));
+        let extra = contract.get_token_extra("fan001".to_string()).unwrap();
+        assert_eq!(extra.group_id, "theosis");
+        assert_eq!(extra.held_since, U64(1_000));
+        assert_eq!(extra.tier, None);
+        let served = contract.nft_token("fan001".to_string()).unwrap().metadata.unwrap().extra.unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&served).unwrap()["raw"], "not json");
+
+        // A JSON object keeps its own keys next to the record's, which win on a clash
+        let mut metadata = sample_token_metadata();
+        metadata.extra = Some(r#"{"group_id":"other","publicKey":"ed25519:abc"}"#.to_string());
+        contract.nft_mint_callback(accounts(3), metadata, "theosis".to_string(), None, None, None, accounts(3));
+        let served = contract.nft_token("fan002".to_string()).unwrap().metadata.unwrap().extra.unwrap();
+        let served: serde_json::Value = serde_json::from_str(&served).unwrap();
+        assert_eq!(served["group_id"], "theosis");
+        assert_eq!(served["publicKey"], "ed25519:abc");
+        assert_eq!(contract.get_token_extra("fan002".to_string()).unwrap().group_id, "theosis");
+
+        // held_since follows the holder
+        let later = 1_000 + 31_536_000_000_000_000;
+        testing_env!(context
+            .attached_deposit(ONE_YOCTONEAR)
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(later)
+            .build());
+        contract.nft_transfer(accounts(2), "fan001".to_string(), None, None);
+        assert_eq!(contract.get_token_extra("fan001".to_string()).unwrap().held_since, U64(later));
+
+        // Burning removes the record
+        testing_env!(context
+            .attached_deposit(BURN_STORAGE_COST)
+            .predecessor_account_id(accounts(2))
+            .build());
+        contract.nft_burn("fan001".to_string()).unwrap();
+        testing_env!(context.block_timestamp(env::block_timestamp() + DEFAULT_BURN_GRACE_PERIOD).build());
+        assert_eq!(contract.finalize_burns(None), 1);
+        assert!(contract.get_token_extra("fan001".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_burn_legacy_token_with_malformed_extra() {
+        let mut context = get_context("theosis.1000fans.near".parse().unwrap());
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
+        contract.initialize(None).unwrap();
+
+        // A token minted without the typed record, carrying garbage in `extra`
+        let mut metadata = sample_token_metadata();
+        metadata.extra = Some("{\"group_id\": 42".to_string());
+        contract.tokens.internal_mint("fan001".to_string(), accounts(1), Some(metadata));
+        let token = contract.nft_token("fan001".to_string()).unwrap();
+        assert_eq!(contract.internal_token_extra(&token).group_id, "theosis");
+
+        testing_env!(context
+            .attached_deposit(BURN_STORAGE_COST)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_burn("fan001".to_string()).unwrap();
+        testing_env!(context.block_timestamp(env::block_timestamp() + DEFAULT_BURN_GRACE_PERIOD).build());
+        assert_eq!(contract.finalize_burns(None), 1);
+        assert!(!contract.owns_token(accounts(1)));
+    }
+
     #[test]
     fn test_burn_unauthorized() {
         let mut context = get_context("theosis.1000fans.near".parse().unwrap());
         testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        contract.initialize();
+        contract.initialize(None).unwrap();
 
         // Mint a token
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string());
+        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string());
+        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
 
         // Attempt to burn by unauthorized account
         testing_env!(context
@@ -727,7 +3153,7 @@ mod tests {
             .predecessor_account_id(accounts(2))
             .build());
         let burn_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            contract.nft_burn("fan001".to_string());
+            contract.nft_burn("fan001".to_string()).unwrap();
         }));
         assert!(burn_result.is_err(), "Burn should fail for non-owner");
     }
@@ -737,19 +3163,19 @@ mod tests {
         let mut context = get_context("theosis.1000fans.near".parse().unwrap());
         testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        contract.initialize();
+        contract.initialize(None).unwrap();
 
         // Mint a token
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string());
+        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string());
+        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
         let token_id = "fan001".to_string();
 
         // Fail: transfer within one year
@@ -777,12 +3203,12 @@ mod tests {
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        let _ = contract.nft_mint(accounts(3), sample_token_metadata(), "theosis".to_string());
+        let _ = contract.nft_mint(accounts(3), sample_token_metadata(), "theosis".to_string(), None, None);
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        contract.nft_mint_callback(accounts(3), sample_token_metadata(), "theosis".to_string());
+        contract.nft_mint_callback(accounts(3), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(3));
         let new_token_id = "fan002".to_string();
         testing_env!(context
             .attached_deposit(ONE_YOCTONEAR)
@@ -800,19 +3226,19 @@ mod tests {
         let mut context = get_context("theosis.1000fans.near".parse().unwrap());
         testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        contract.initialize();
+        contract.initialize(None).unwrap();
 
         // Mint a token
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string());
+        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string());
+        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
         let token_id = "fan001".to_string();
 
         // Fail: transfer_call within one year
@@ -830,12 +3256,12 @@ mod tests {
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        let _ = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string());
+        let _ = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None);
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string());
+        contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(2));
         let new_token_id = "fan002".to_string();
         testing_env!(context
             .attached_deposit(ONE_YOCTONEAR)
@@ -856,19 +3282,19 @@ mod tests {
             .attached_deposit(MINT_STORAGE_COST)
             .build());
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        contract.initialize();
+        contract.initialize(None).unwrap();
 
         // Mint a token
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string());
+        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string());
+        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
         let token_id = "fan001".to_string();
 
         // Approve
@@ -923,29 +3349,29 @@ mod tests {
         let mut context = get_context("theosis.1000fans.near".parse().unwrap());
         testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        contract.initialize();
+        contract.initialize(None).unwrap();
 
         // Mint tokens
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string());
+        let _ = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string());
+        contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        let _ = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string());
+        let _ = contract.nft_mint(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None);
         testing_env!(context
             .attached_deposit(MINT_STORAGE_COST)
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string());
+        contract.nft_mint_callback(accounts(2), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(2));
 
         testing_env!(context.is_view(true).build());
         assert_eq!(contract.nft_total_supply(), U128::from(3));
@@ -962,14 +3388,14 @@ mod tests {
         let mut context = get_context("theosis.1000fans.near".parse().unwrap());
         testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        contract.initialize();
-        assert_eq!(contract.get_mint_storage_cost(), U128(7_000_000_000_000_000_000_000));
+        contract.initialize(None).unwrap();
+        assert_eq!(contract.get_mint_storage_cost(), U128(11_000_000_000_000_000_000_000));
 
         // Test insufficient deposit for initialize
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
         testing_env!(context.attached_deposit(NearToken::from_yoctonear(1)).build());
         let init_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            contract.initialize();
+            contract.initialize(None).unwrap();
         }));
         assert!(init_result.is_err(), "Initialization should fail with insufficient deposit");
     }
@@ -979,17 +3405,20 @@ mod tests {
         let mut context = get_context("theosis.1000fans.near".parse().unwrap());
         testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
         let mut contract = Contract::new("theosis.devbot.near".parse().unwrap());
-        contract.initialize();
+        contract.initialize(None).unwrap();
 
         // Test insufficient deposit for mint
         testing_env!(context
             .attached_deposit(NearToken::from_yoctonear(1))
             .predecessor_account_id("theosis.1000fans.near".parse().unwrap())
             .build());
-        let mint_result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string());
-        assert!(mint_result.is_ok(), "nft_mint should succeed with minimal deposit");
+        let result = contract.nft_mint(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None);
+        assert_eq!(
+            result.err().unwrap(),
+            FansError::InsufficientDeposit { attached: U128(1), required: contract.get_mint_storage_cost() }
+        );
         let callback_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string());
+            contract.nft_mint_callback(accounts(1), sample_token_metadata(), "theosis".to_string(), None, None, None, accounts(1));
         }));
         assert!(callback_result.is_err(), "nft_mint_callback should fail with insufficient deposit");
     }
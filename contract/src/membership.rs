@@ -0,0 +1,187 @@
+// Membership backends: contracts that mirror who holds a token, such as the devbot chat gate,
+// a NEAR Social group or a self-hosted chat relay
+use crate::events::{ConfigUpdated, GroupSyncFailed, GroupSyncSucceeded};
+use crate::*;
+
+/// A contract kept in sync with token holders. Every method is called with
+/// `{"group_id": ..., "user_id": ...}` (`register_method` with `{"group_id": ...}` only).
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MembershipBackend {
+    pub contract_id: AccountId,
+    /// Called once for the club's group when the backend is added; skipped when `None`
+    pub register_method: Option<String>,
+    pub add_method: String,
+    pub revoke_method: String,
+    pub register: CallBudget,
+    pub add: CallBudget,
+    pub revoke: CallBudget,
+}
+
+impl MembershipBackend {
+    /// The devbot contract, with the budgets it has always been called with
+    pub fn devbot(contract_id: AccountId) -> Self {
+        Self {
+            contract_id,
+            register_method: Some("register_group".to_string()),
+            add_method: "add_group_member".to_string(),
+            revoke_method: "revoke_group_member".to_string(),
+            register: CallBudget::new(30, NearToken::from_millinear(10)), // 0.01 NEAR
+            add: CallBudget::new(50, NearToken::from_millinear(25)), // 0.025 NEAR
+            revoke: CallBudget::new(10, NearToken::from_yoctonear(0)),
+        }
+    }
+
+    fn call(&self, action: SyncAction, group_id: &str, account_id: &AccountId) -> Option<Promise> {
+        let (method, budget, args) = match action {
+            SyncAction::Register => (
+                self.register_method.as_ref()?,
+                self.register,
+                serde_json::json!({ "group_id": group_id }),
+            ),
+            SyncAction::Add => (&self.add_method, self.add, serde_json::json!({ "group_id": group_id, "user_id": account_id })),
+            SyncAction::Revoke => (&self.revoke_method, self.revoke, serde_json::json!({ "group_id": group_id, "user_id": account_id })),
+            SyncAction::SetTier => return None,
+        };
+        Some(Promise::new(self.contract_id.clone()).function_call(
+            method.clone(),
+            args.to_string().into_bytes(),
+            budget.deposit,
+            budget.gas,
+        ))
+    }
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncAction {
+    Register,
+    Add,
+    Revoke,
+    /// A member's tier changed; only the devbot is told, through `set_group_member_tier`
+    SetTier,
+}
+
+/// Outcome of the last call made to one backend for one account
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncStatus {
+    pub action: SyncAction,
+    pub success: bool,
+    pub updated_at: U64,
+}
+
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MembershipSyncView {
+    pub backend_id: AccountId,
+    pub status: Option<SyncStatus>,
+}
+
+#[near]
+impl Contract {
+    /// Registers a backend (replacing any with the same contract) and the club's groups there
+    #[payable]
+    #[handle_result]
+    pub fn add_membership_backend(&mut self, backend: MembershipBackend) -> Result<(), FansError> {
+        self.assert_owner("Only owner can add membership backends")?;
+        self.membership_backends.retain(|existing| existing.contract_id != backend.contract_id);
+        self.membership_backends.push(backend.clone());
+        ConfigUpdated { field: "membership_backends".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        self.internal_register_groups(&backend);
+        Ok(())
+    }
+
+    #[payable]
+    #[handle_result]
+    pub fn remove_membership_backend(&mut self, contract_id: AccountId) -> Result<(), FansError> {
+        self.assert_owner("Only owner can remove membership backends")?;
+        let count = self.membership_backends.len();
+        self.membership_backends.retain(|backend| backend.contract_id != contract_id);
+        if self.membership_backends.len() < count {
+            ConfigUpdated { field: "membership_backends".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        }
+        Ok(())
+    }
+
+    pub fn get_membership_backends(&self) -> Vec<MembershipBackend> {
+        self.membership_backends.clone()
+    }
+
+    /// Last known outcome on every backend for `account_id`
+    pub fn get_membership_sync(&self, account_id: AccountId) -> Vec<MembershipSyncView> {
+        self.membership_backends
+            .iter()
+            .map(|backend| MembershipSyncView {
+                status: self.membership_sync.get(&(account_id.clone(), backend.contract_id.clone())),
+                backend_id: backend.contract_id.clone(),
+            })
+            .collect()
+    }
+
+    #[private]
+    pub fn on_membership_synced(&mut self, backend_id: AccountId, group_id: String, account_id: AccountId, action: SyncAction) {
+        let success = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        let status = SyncStatus { action, success, updated_at: U64(env::block_timestamp()) };
+        self.membership_sync.insert(&(account_id.clone(), backend_id.clone()), &status);
+        if success {
+            GroupSyncSucceeded { backend_id, group_id, account_id, action }.emit();
+        } else {
+            GroupSyncFailed { backend_id, group_id, account_id, action }.emit();
+        }
+    }
+}
+
+impl Contract {
+    /// Sends `action` for `account_id` to every backend. Each call reports back through
+    /// `on_membership_synced`, so one failing backend does not affect the others.
+    pub(crate) fn internal_sync_membership(&self, group_id: &str, account_id: &AccountId, action: SyncAction) {
+        for backend in self.membership_backends.iter() {
+            if let Some(promise) = backend.call(action, group_id, account_id) {
+                promise.then(self.internal_sync_callback(&backend.contract_id, group_id, account_id, action));
+            }
+        }
+    }
+
+    /// Registers every group of the club on `backend` alone, as when it joins the list
+    pub(crate) fn internal_register_groups(&self, backend: &MembershipBackend) {
+        let member_id = env::current_account_id();
+        for group_id in self.groups.keys() {
+            if let Some(promise) = backend.call(SyncAction::Register, &group_id, &member_id) {
+                promise.then(self.internal_sync_callback(&backend.contract_id, &group_id, &member_id, SyncAction::Register));
+            }
+        }
+    }
+
+    /// Registers the group on every backend, then adds `account_id` to it
+    pub(crate) fn internal_register_and_add(&self, group_id: &str, account_id: &AccountId) {
+        for backend in self.membership_backends.iter() {
+            let add = backend.call(SyncAction::Add, group_id, account_id).unwrap();
+            let promise = match backend.call(SyncAction::Register, group_id, account_id) {
+                Some(register) => register.then(add),
+                None => add,
+            };
+            promise.then(self.internal_sync_callback(&backend.contract_id, group_id, account_id, SyncAction::Add));
+        }
+    }
+
+    /// Gas the fan-out of `action` needs, including the callbacks
+    pub(crate) fn internal_sync_gas(&self, action: SyncAction) -> Gas {
+        self.membership_backends
+            .iter()
+            .map(|backend| match action {
+                SyncAction::Register => backend.register.gas.saturating_add(backend.add.gas),
+                SyncAction::Add => backend.add.gas,
+                SyncAction::Revoke => backend.revoke.gas,
+                SyncAction::SetTier => Gas::from_gas(0),
+            })
+            .fold(Gas::from_gas(0), |total, gas| total.saturating_add(gas).saturating_add(self.devbot_config.sync_callback_gas))
+    }
+
+    pub(crate) fn internal_sync_callback(&self, backend_id: &AccountId, group_id: &str, account_id: &AccountId, action: SyncAction) -> Promise {
+        Self::ext(env::current_account_id())
+            .with_static_gas(self.devbot_config.sync_callback_gas)
+            .on_membership_synced(backend_id.clone(), group_id.to_string(), account_id.clone(), action)
+    }
+}
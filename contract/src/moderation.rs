@@ -0,0 +1,137 @@
+// Moderation: owner-appointed moderators can suspend a member for a while instead of burning
+// their token. A suspended member keeps the token and its number but loses group access and
+// cannot transfer or approve it.
+use crate::events::{ConfigUpdated, MemberReinstated, MemberSuspended};
+use crate::membership::SyncAction;
+use crate::*;
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suspension {
+    pub suspended_by: AccountId,
+    pub reason: String,
+    pub since: U64,
+    /// Block timestamp (ns) at which the suspension runs out on its own
+    pub until: U64,
+    /// Set when the member was reinstated, before or after `until`
+    pub reinstated_at: Option<U64>,
+}
+
+#[near]
+impl Contract {
+    #[payable]
+    #[handle_result]
+    pub fn add_moderator(&mut self, account_id: AccountId) -> Result<(), FansError> {
+        self.assert_owner("Only owner can add moderators")?;
+        self.moderators.insert(&account_id);
+        ConfigUpdated { field: "moderators".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    #[payable]
+    #[handle_result]
+    pub fn remove_moderator(&mut self, account_id: AccountId) -> Result<(), FansError> {
+        self.assert_owner("Only owner can remove moderators")?;
+        self.moderators.remove(&account_id);
+        ConfigUpdated { field: "moderators".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    pub fn get_moderators(&self) -> Vec<AccountId> {
+        self.moderators.to_vec()
+    }
+
+    /// Revokes the member's group access until they are reinstated, by a moderator at any time
+    /// or by themselves once `until` has passed. The token stays with them but cannot be
+    /// transferred or approved before `until`.
+    #[payable]
+    #[handle_result]
+    pub fn suspend_member(&mut self, account_id: AccountId, until: U64, reason: String) -> Result<(), FansError> {
+        self.assert_moderator("Only moderators can suspend members")?;
+        let group_id = self.internal_member_group(&account_id).ok_or(FansError::TokenNotFound)?;
+        if self.is_suspended(&account_id) {
+            return Err(FansError::InvalidSuspension("member is already suspended".to_string()));
+        }
+        if until.0 <= env::block_timestamp() {
+            return Err(FansError::InvalidSuspension("until must be in the future".to_string()));
+        }
+        let suspended_by = env::predecessor_account_id();
+        let mut history = self.suspensions.get(&account_id).unwrap_or_default();
+        history.push(Suspension {
+            suspended_by: suspended_by.clone(),
+            reason: reason.clone(),
+            since: U64(env::block_timestamp()),
+            until,
+            reinstated_at: None,
+        });
+        self.suspensions.insert(&account_id, &history);
+        self.internal_sync_membership(&group_id, &account_id, SyncAction::Revoke);
+        MemberSuspended { account_id, suspended_by, until, reason }.emit();
+        Ok(())
+    }
+
+    /// Ends the member's suspension and restores their group access. Moderators can do this at
+    /// any time, the member themselves once the suspension has run out.
+    #[payable]
+    #[handle_result]
+    pub fn reinstate_member(&mut self, account_id: AccountId) -> Result<(), FansError> {
+        let caller = env::predecessor_account_id();
+        let mut history = self.suspensions.get(&account_id).unwrap_or_default();
+        let suspension = match history.last_mut() {
+            Some(suspension) if suspension.reinstated_at.is_none() => suspension,
+            _ => return Err(FansError::NotSuspended),
+        };
+        let expired = env::block_timestamp() >= suspension.until.0;
+        if !(self.is_moderator(caller.clone()) || (caller == account_id && expired)) {
+            return Err(FansError::Unauthorized("Only moderators can reinstate members".to_string()));
+        }
+        suspension.reinstated_at = Some(U64(env::block_timestamp()));
+        self.suspensions.insert(&account_id, &history);
+        if let Some(group_id) = self.internal_member_group(&account_id) {
+            self.internal_sync_membership(&group_id, &account_id, SyncAction::Add);
+        }
+        MemberReinstated { account_id, reinstated_by: caller }.emit();
+        Ok(())
+    }
+
+    /// True for the owner and the accounts they appointed
+    pub fn is_moderator(&self, account_id: AccountId) -> bool {
+        account_id == self.tokens.owner_id || self.moderators.contains(&account_id)
+    }
+
+    /// Holds a token and has no suspension left open. A suspension that ran out still counts
+    /// until the member is reinstated, since that is what restores their group access.
+    pub fn is_active_member(&self, account_id: AccountId) -> bool {
+        self.owns_token(account_id.clone()) && self.internal_open_suspension(&account_id).is_none()
+    }
+
+    /// Every suspension of the account, oldest first
+    pub fn get_suspensions(&self, account_id: AccountId) -> Vec<Suspension> {
+        self.suspensions.get(&account_id).unwrap_or_default()
+    }
+}
+
+impl Contract {
+    pub(crate) fn is_suspended(&self, account_id: &AccountId) -> bool {
+        self.internal_open_suspension(account_id)
+            .is_some_and(|suspension| env::block_timestamp() < suspension.until.0)
+    }
+
+    /// The latest suspension, unless the member was reinstated since
+    fn internal_open_suspension(&self, account_id: &AccountId) -> Option<Suspension> {
+        self.suspensions.get(account_id)?.pop().filter(|suspension| suspension.reinstated_at.is_none())
+    }
+
+    /// Group of the token the account holds
+    fn internal_member_group(&self, account_id: &AccountId) -> Option<String> {
+        let token = self.tokens.nft_tokens_for_owner(account_id.clone(), None, Some(1)).pop()?;
+        Some(self.internal_token_extra(&token).group_id)
+    }
+
+    fn assert_moderator(&self, reason: &str) -> Result<(), FansError> {
+        if !self.is_moderator(env::predecessor_account_id()) {
+            return Err(FansError::Unauthorized(reason.to_string()));
+        }
+        Ok(())
+    }
+}
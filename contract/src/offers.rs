@@ -0,0 +1,249 @@
+// Offers: a fan escrows NEAR for a specific token, or for any holder's, and the holder sells by
+// accepting; the escrow pays the holder and the token moves to the buyer
+use crate::events::{OfferAccepted, OfferCancelled, OfferMade};
+use crate::*;
+use near_sdk::assert_one_yocto;
+
+/// Open offers a single token may have, so that burning it can refund them all
+const MAX_OFFERS_PER_TOKEN: u64 = 50;
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Offer {
+    pub buyer_id: AccountId,
+    /// Token the offer is for, `None` when any holder may accept it
+    pub token_id: Option<TokenId>,
+    /// Escrowed for the holder who accepts, in yoctoNEAR
+    pub price: U128,
+    /// Block timestamp (ns) after which the offer can no longer be accepted
+    pub expires_at: Option<U64>,
+    /// Storage the buyer paid for the offer, in yoctoNEAR; returned with the price when it is
+    /// cancelled
+    pub deposit: U128,
+    pub created_at: U64,
+}
+
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct OfferView {
+    pub offer_id: U64,
+    #[serde(flatten)]
+    pub offer: Offer,
+}
+
+#[near]
+impl Contract {
+    /// Offers `price` for `token_id`, or for any holder's token when it is `None`. Attach the
+    /// price plus enough to cover the offer's storage; both stay in escrow until the offer is
+    /// accepted or cancelled, or its token is burned.
+    #[payable]
+    #[handle_result]
+    pub fn make_offer(&mut self, token_id: Option<TokenId>, price: U128, expires_at: Option<U64>) -> Result<U64, FansError> {
+        if self.paused {
+            return Err(FansError::Paused);
+        }
+        let buyer_id = env::predecessor_account_id();
+        if self.owns_token(buyer_id.clone()) {
+            return Err(FansError::AlreadyOwns);
+        }
+        self.internal_assert_not_banned(&buyer_id)?;
+        if let Some(token_id) = &token_id {
+            self.tokens.owner_by_id.get(token_id).ok_or(FansError::TokenNotFound)?;
+            if self.offers_by_token.get(token_id).is_some_and(|offer_ids| offer_ids.len() >= MAX_OFFERS_PER_TOKEN) {
+                return Err(FansError::InvalidOffer("the token has too many open offers".to_string()));
+            }
+        }
+        if price.0 == 0 {
+            return Err(FansError::InvalidOffer("price must be above zero".to_string()));
+        }
+        // An offer the cap already refuses could never be accepted. A multiple cap depends on
+        // the token, so offers open to any holder are only held to an absolute one.
+        match (&token_id, self.resale_cap) {
+            (Some(token_id), _) => self.internal_assert_within_resale_cap(token_id, price.0)?,
+            (None, Some(ResaleCap::Absolute(cap))) if price.0 > cap.0 => {
+                return Err(FansError::ResaleCapExceeded { cap });
+            }
+            (None, _) => {}
+        }
+        if expires_at.is_some_and(|expires_at| expires_at.0 <= env::block_timestamp()) {
+            return Err(FansError::InvalidOffer("expires_at must be in the future".to_string()));
+        }
+
+        let deposit = env::attached_deposit().as_yoctonear();
+        let initial_storage = env::storage_usage();
+        let offer_id = self.next_offer_id;
+        let mut offer = Offer {
+            buyer_id: buyer_id.clone(),
+            token_id,
+            price,
+            expires_at,
+            deposit: U128(0),
+            created_at: U64(env::block_timestamp()),
+        };
+        self.offers.insert(&offer_id, &offer);
+        self.internal_index_offer(offer_id, &offer);
+        let storage_cost =
+            env::storage_usage().saturating_sub(initial_storage) as u128 * env::storage_byte_cost().as_yoctonear();
+        if deposit < price.0 + storage_cost {
+            self.internal_remove_offer(offer_id);
+            return Err(FansError::InsufficientDeposit { attached: U128(deposit), required: U128(price.0 + storage_cost) });
+        }
+        offer.deposit = U128(storage_cost);
+        self.offers.insert(&offer_id, &offer);
+        self.next_offer_id += 1;
+        if deposit > price.0 + storage_cost {
+            Promise::new(buyer_id).transfer(NearToken::from_yoctonear(deposit - price.0 - storage_cost));
+        }
+        OfferMade { offer_id: U64(offer_id), buyer_id: offer.buyer_id, token_id: offer.token_id, price, expires_at }.emit();
+        Ok(U64(offer_id))
+    }
+
+    /// Sells the caller's `token_id` to the buyer for the escrowed price. The token has to be
+    /// transferable under its lock rules, the buyer may not hold a token already, and the price
+    /// has to be within the resale cap. Requires 1 yoctoNEAR, like a transfer.
+    #[payable]
+    #[handle_result]
+    pub fn accept_offer(&mut self, offer_id: U64, token_id: TokenId) -> Result<(), FansError> {
+        assert_one_yocto();
+        if self.paused {
+            return Err(FansError::Paused);
+        }
+        let offer = self.offers.get(&offer_id.0).ok_or(FansError::UnknownOffer)?;
+        if offer.expires_at.is_some_and(|expires_at| env::block_timestamp() >= expires_at.0) {
+            return Err(FansError::OfferExpired);
+        }
+        let seller_id = env::predecessor_account_id();
+        let token = self.tokens.nft_token(token_id.clone()).ok_or(FansError::TokenNotFound)?;
+        if token.owner_id != seller_id {
+            return Err(FansError::Unauthorized("Only the token owner can accept an offer for it".to_string()));
+        }
+        if offer.token_id.as_ref().is_some_and(|offer_token_id| *offer_token_id != token_id) {
+            return Err(FansError::InvalidOffer(format!("the offer is not for {}", token_id)));
+        }
+        if self.owns_token(offer.buyer_id.clone()) {
+            return Err(FansError::AlreadyOwns);
+        }
+        self.internal_assert_not_banned(&offer.buyer_id)?;
+        self.internal_assert_within_resale_cap(&token_id, offer.price.0)?;
+        self.assert_transferable(&token);
+
+        self.internal_remove_offer(offer_id.0);
+        self.tokens.internal_transfer(&seller_id, &offer.buyer_id, &token_id, None, None);
+        self.internal_on_transfer(&token_id, &seller_id);
+        Promise::new(seller_id.clone()).transfer(NearToken::from_yoctonear(offer.price.0));
+        Promise::new(offer.buyer_id.clone()).transfer(NearToken::from_yoctonear(offer.deposit.0));
+        OfferAccepted { offer_id, token_id, seller_id, buyer_id: offer.buyer_id, price: offer.price }.emit();
+        Ok(())
+    }
+
+    /// Withdraws an offer, refunding the price and the storage deposit to the buyer. Anyone can
+    /// clear an expired offer.
+    #[payable]
+    #[handle_result]
+    pub fn cancel_offer(&mut self, offer_id: U64) -> Result<(), FansError> {
+        let offer = self.offers.get(&offer_id.0).ok_or(FansError::UnknownOffer)?;
+        let caller = env::predecessor_account_id();
+        let expired = offer.expires_at.is_some_and(|expires_at| env::block_timestamp() >= expires_at.0);
+        if caller != offer.buyer_id && !expired {
+            return Err(FansError::Unauthorized("Only the buyer can cancel an offer".to_string()));
+        }
+        self.internal_remove_offer(offer_id.0);
+        Promise::new(offer.buyer_id).transfer(NearToken::from_yoctonear(offer.price.0 + offer.deposit.0));
+        OfferCancelled { offer_id, cancelled_by: caller }.emit();
+        Ok(())
+    }
+
+    pub fn get_offer(&self, offer_id: U64) -> Option<Offer> {
+        self.offers.get(&offer_id.0)
+    }
+
+    /// Offers the token's holder can accept: those for the token, then those open to any holder
+    pub fn get_offers_for_token(&self, token_id: TokenId, from_index: Option<U128>, limit: Option<u64>) -> Vec<OfferView> {
+        let for_token = self.offers_by_token.get(&token_id);
+        let offer_ids = for_token.iter().flat_map(|offer_ids| offer_ids.iter()).chain(self.open_offers.iter());
+        self.internal_offer_views(offer_ids, from_index, limit)
+    }
+
+    pub fn get_offers_by_account(&self, account_id: AccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<OfferView> {
+        let by_account = self.offers_by_account.get(&account_id);
+        self.internal_offer_views(by_account.iter().flat_map(|offer_ids| offer_ids.iter()), from_index, limit)
+    }
+}
+
+impl Contract {
+    fn internal_offer_views(&self, offer_ids: impl Iterator<Item = u64>, from_index: Option<U128>, limit: Option<u64>) -> Vec<OfferView> {
+        let start = from_index.map_or(0, |index| index.0 as usize);
+        offer_ids
+            .skip(start)
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .filter_map(|offer_id| self.offers.get(&offer_id).map(|offer| OfferView { offer_id: U64(offer_id), offer }))
+            .collect()
+    }
+
+    fn internal_index_offer(&mut self, offer_id: u64, offer: &Offer) {
+        match &offer.token_id {
+            Some(token_id) => {
+                let mut offer_ids = self.offers_by_token.get(token_id).unwrap_or_else(|| {
+                    UnorderedSet::new(StorageKey::OffersByTokenInner { token_hash: env::sha256_array(token_id.as_bytes()) })
+                });
+                offer_ids.insert(&offer_id);
+                self.offers_by_token.insert(token_id, &offer_ids);
+            }
+            None => {
+                self.open_offers.insert(&offer_id);
+            }
+        }
+        let mut offer_ids = self.offers_by_account.get(&offer.buyer_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::OffersByAccountInner { account_hash: env::sha256_array(offer.buyer_id.as_bytes()) })
+        });
+        offer_ids.insert(&offer_id);
+        self.offers_by_account.insert(&offer.buyer_id, &offer_ids);
+    }
+
+    /// Cancels the offers for `token_id` and refunds their buyers, as a burned token can no
+    /// longer be sold and its ID may be minted again
+    pub(crate) fn internal_cancel_token_offers(&mut self, token_id: &TokenId) {
+        let offer_ids = match self.offers_by_token.get(token_id) {
+            Some(offer_ids) => offer_ids.to_vec(),
+            None => return,
+        };
+        for offer_id in offer_ids {
+            if let Some(offer) = self.offers.get(&offer_id) {
+                self.internal_remove_offer(offer_id);
+                Promise::new(offer.buyer_id).transfer(NearToken::from_yoctonear(offer.price.0 + offer.deposit.0));
+                OfferCancelled { offer_id: U64(offer_id), cancelled_by: env::predecessor_account_id() }.emit();
+            }
+        }
+    }
+
+    /// Drops the offer and its index entries
+    fn internal_remove_offer(&mut self, offer_id: u64) {
+        let offer = match self.offers.remove(&offer_id) {
+            Some(offer) => offer,
+            None => return,
+        };
+        match &offer.token_id {
+            Some(token_id) => {
+                if let Some(mut offer_ids) = self.offers_by_token.get(token_id) {
+                    offer_ids.remove(&offer_id);
+                    if offer_ids.is_empty() {
+                        self.offers_by_token.remove(token_id);
+                    } else {
+                        self.offers_by_token.insert(token_id, &offer_ids);
+                    }
+                }
+            }
+            None => {
+                self.open_offers.remove(&offer_id);
+            }
+        }
+        if let Some(mut offer_ids) = self.offers_by_account.get(&offer.buyer_id) {
+            offer_ids.remove(&offer_id);
+            if offer_ids.is_empty() {
+                self.offers_by_account.remove(&offer.buyer_id);
+            } else {
+                self.offers_by_account.insert(&offer.buyer_id, &offer_ids);
+            }
+        }
+    }
+}
@@ -0,0 +1,31 @@
+// The club registry, kept by the factory that created the club, which answers which clubs an
+// account belongs to
+use crate::*;
+
+const RECORD_MEMBERSHIP_GAS: Gas = Gas::from_tgas(10);
+
+#[near_sdk::ext_contract(ext_club_registry)]
+pub trait ExtClubRegistry {
+    fn record_membership(&mut self, account_id: AccountId, member: bool);
+}
+
+impl Contract {
+    /// Tells the registry that `account_id` joined or left the club. Clubs deployed by hand
+    /// have no registry and skip this. The first holder is recorded by the factory itself from
+    /// the result of `initialize`.
+    pub(crate) fn internal_notify_registry(&self, account_id: &AccountId, member: bool) {
+        if let Some(registry_id) = &self.factory_id {
+            ext_club_registry::ext(registry_id.clone())
+                .with_static_gas(RECORD_MEMBERSHIP_GAS)
+                .record_membership(account_id.clone(), member);
+        }
+    }
+
+    pub(crate) fn internal_registry_gas(&self) -> Gas {
+        if self.factory_id.is_some() {
+            RECORD_MEMBERSHIP_GAS
+        } else {
+            Gas::from_gas(0)
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Resale caps: the owner can limit what a token may be resold for, so fans trade at fair prices.
+// Marketplaces settle through NEP-199 payouts and fans through offers, which both refuse prices above the cap.
+use crate::events::ConfigUpdated;
+use crate::*;
+use std::collections::HashMap;
+
+/// Highest price a token may be resold for
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResaleCap {
+    /// The same ceiling for every token, in yoctoNEAR
+    Absolute(U128),
+    /// A multiple of the price paid for the token, in basis points (15_000 is 1.5x). Tokens
+    /// that were free have nothing to multiply and stay uncapped.
+    MintPriceMultiple(u32),
+}
+
+/// NEP-199 payout: what each account receives out of a sale's balance
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Payout {
+    pub payout: HashMap<AccountId, U128>,
+}
+
+#[near]
+impl Contract {
+    /// Sets the cap on resale prices, or lifts it with `None`
+    #[payable]
+    #[handle_result]
+    pub fn set_resale_cap(&mut self, cap: Option<ResaleCap>) -> Result<(), FansError> {
+        self.assert_owner("Only owner can set the resale cap")?;
+        self.resale_cap = cap;
+        ConfigUpdated { field: "resale_cap".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    pub fn get_resale_cap_config(&self) -> Option<ResaleCap> {
+        self.resale_cap
+    }
+
+    /// Highest price the token may be resold for, in yoctoNEAR, or `None` when uncapped
+    pub fn get_resale_cap(&self, token_id: TokenId) -> Option<U128> {
+        let cap = self.resale_cap?;
+        let token = self.tokens.nft_token(token_id)?;
+        match cap {
+            ResaleCap::Absolute(amount) => Some(amount),
+            ResaleCap::MintPriceMultiple(basis_points) => {
+                // Free tokens have nothing to multiply, and a cap past u128 caps nothing
+                let mint_price = self.internal_token_extra(&token).mint_price.0;
+                mint_price
+                    .checked_mul(basis_points as u128)
+                    .filter(|_| mint_price > 0)
+                    .map(|cap| U128(cap / 10_000))
+            }
+        }
+    }
+
+    /// How `balance` from a sale of the token is split. Without royalties it all goes to the
+    /// holder, and balances above the resale cap are refused.
+    #[handle_result]
+    pub fn nft_payout(&self, token_id: TokenId, balance: U128, max_len_payout: Option<u32>) -> Result<Payout, FansError> {
+        let owner_id = self.tokens.owner_by_id.get(&token_id).ok_or(FansError::TokenNotFound)?;
+        self.internal_assert_within_resale_cap(&token_id, balance.0)?;
+        if max_len_payout == Some(0) {
+            return Err(FansError::InvalidPayout("max_len_payout must allow the holder".to_string()));
+        }
+        Ok(Payout { payout: HashMap::from([(owner_id, balance)]) })
+    }
+
+    /// Transfers the token like `nft_transfer` and returns the payout for the sale's balance,
+    /// which marketplaces call once the buyer has paid
+    #[payable]
+    #[handle_result]
+    pub fn nft_transfer_payout(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        balance: U128,
+        max_len_payout: Option<u32>,
+    ) -> Result<Payout, FansError> {
+        let payout = self.nft_payout(token_id.clone(), balance, max_len_payout)?;
+        self.nft_transfer(receiver_id, token_id, approval_id, memo);
+        Ok(payout)
+    }
+}
+
+impl Contract {
+    /// Fails with `ResaleCapExceeded` when `price` is above the token's resale cap
+    pub(crate) fn internal_assert_within_resale_cap(&self, token_id: &TokenId, price: u128) -> Result<(), FansError> {
+        match self.get_resale_cap(token_id.clone()) {
+            Some(cap) if price > cap.0 => Err(FansError::ResaleCapExceeded { cap }),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// Sponsored calls: fans onboarded with empty accounts call through a NEP-366 relayer, which
+// pays the gas, while the storage their calls use comes from a balance the owner funds
+use crate::events::{CallSponsored, ConfigUpdated};
+use crate::*;
+use near_contract_standards::non_fungible_token::approval::ext_nft_approval_receiver;
+use near_contract_standards::non_fungible_token::bytes_for_approved_account_id;
+
+const GAS_FOR_NFT_APPROVE: Gas = Gas::from_tgas(10);
+
+#[near]
+impl Contract {
+    /// Adds the attached deposit to the balance sponsored calls draw their storage from
+    #[payable]
+    #[handle_result]
+    pub fn fund_sponsorship(&mut self) -> Result<(), FansError> {
+        self.assert_owner("Only owner can fund sponsorship")?;
+        self.sponsor_balance += env::attached_deposit().as_yoctonear();
+        ConfigUpdated { field: "sponsor_balance".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    #[payable]
+    #[handle_result]
+    pub fn withdraw_sponsorship(&mut self, amount: U128) -> Result<Promise, FansError> {
+        self.assert_owner("Only owner can withdraw sponsorship")?;
+        if amount.0 > self.sponsor_balance {
+            return Err(FansError::SponsorBalanceTooLow { available: U128(self.sponsor_balance), required: amount });
+        }
+        self.sponsor_balance -= amount.0;
+        ConfigUpdated { field: "sponsor_balance".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(Promise::new(env::predecessor_account_id()).transfer(NearToken::from_yoctonear(amount.0)))
+    }
+
+    /// Sponsors the storage of `calls` calls by `account_id` or mints for it, replacing whatever
+    /// it had left
+    #[payable]
+    #[handle_result]
+    pub fn set_sponsored_calls(&mut self, account_id: AccountId, calls: u32) -> Result<(), FansError> {
+        self.assert_owner("Only owner can set sponsored calls")?;
+        if calls == 0 {
+            self.sponsored_calls.remove(&account_id);
+        } else {
+            self.sponsored_calls.insert(&account_id, &calls);
+        }
+        ConfigUpdated { field: "sponsored_calls".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    pub fn get_sponsored_calls(&self, account_id: AccountId) -> u32 {
+        self.sponsored_calls.get(&account_id).unwrap_or(0)
+    }
+
+    pub fn get_sponsor_balance(&self) -> U128 {
+        U128(self.sponsor_balance)
+    }
+}
+
+impl Contract {
+    /// Pays `storage` out of the sponsor balance when `account_id` has sponsored calls left and
+    /// did not sign the transaction, using up one call. Returns what was paid, 0 when not
+    /// sponsored. Prices are never sponsored and still have to be attached.
+    pub(crate) fn internal_sponsor_storage(&mut self, account_id: &AccountId, storage: u128) -> u128 {
+        // Someone else signed, such as a relayer submitting the fan's delegate action or an agent
+        // minting for the fan. An account that signs its own transactions pays its own storage.
+        if storage == 0 || env::signer_account_id() == *account_id {
+            return 0;
+        }
+        let calls = self.sponsored_calls.get(account_id).unwrap_or(0);
+        if calls == 0 || self.sponsor_balance < storage {
+            return 0;
+        }
+        if calls == 1 {
+            self.sponsored_calls.remove(account_id);
+        } else {
+            self.sponsored_calls.insert(account_id, &(calls - 1));
+        }
+        self.sponsor_balance -= storage;
+        CallSponsored { account_id: account_id.clone(), amount: U128(storage) }.emit();
+        storage
+    }
+
+    /// `nft_approve` with nothing attached. A sponsored caller has the approval's storage paid
+    /// for, anyone else gets the standard's deposit check.
+    pub(crate) fn internal_approve(&mut self, token_id: TokenId, account_id: AccountId, msg: Option<String>) -> Option<Promise> {
+        let storage = bytes_for_approved_account_id(&account_id) as u128 * env::storage_byte_cost().as_yoctonear();
+        if self.internal_sponsor_storage(&env::predecessor_account_id(), storage) == 0 {
+            return self.tokens.nft_approve(token_id, account_id, msg);
+        }
+        let owner_id = self.tokens.owner_by_id.get(&token_id).unwrap_or_else(|| FansError::TokenNotFound.panic());
+        if env::predecessor_account_id() != owner_id {
+            FansError::Unauthorized("Predecessor must be token owner.".to_string()).panic();
+        }
+        let approvals_by_id = self.tokens.approvals_by_id.as_mut().expect("Approvals should exist");
+        let next_approval_id_by_id = self.tokens.next_approval_id_by_id.as_mut().expect("Approvals should exist");
+        let mut approved_account_ids = approvals_by_id.get(&token_id).unwrap_or_default();
+        let approval_id = next_approval_id_by_id.get(&token_id).unwrap_or(1);
+        approved_account_ids.insert(account_id.clone(), approval_id);
+        approvals_by_id.insert(&token_id, &approved_account_ids);
+        next_approval_id_by_id.insert(&token_id, &(approval_id + 1));
+        msg.map(|msg| {
+            ext_nft_approval_receiver::ext(account_id)
+                .with_static_gas(env::prepaid_gas().saturating_sub(GAS_FOR_NFT_APPROVE))
+                .nft_on_approve(token_id, owner_id, approval_id, msg)
+        })
+    }
+}
@@ -0,0 +1,153 @@
+// Token swaps: two holders trade tokens, e.g. for a lucky number, which separate transfers
+// cannot do since neither may receive a token while holding one
+use crate::events::{SwapAccepted, SwapCancelled, SwapProposed};
+use crate::membership::SyncAction;
+use crate::*;
+use near_sdk::assert_one_yocto;
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Swap {
+    pub proposer_id: AccountId,
+    pub token_id: TokenId,
+    pub counterparty_id: AccountId,
+    pub counterparty_token_id: TokenId,
+    /// Storage the proposer paid for the proposal, in yoctoNEAR; returned when it is settled
+    pub deposit: U128,
+    pub created_at: U64,
+}
+
+#[near]
+impl Contract {
+    /// Offers `my_token` for `their_token`. The holder of `their_token` completes the trade
+    /// with `accept_swap`. Attach enough to cover the proposal's storage; the deposit stands in
+    /// for the 1 yoctoNEAR of `accept_swap`, so a function-call key cannot propose either.
+    #[payable]
+    #[handle_result]
+    pub fn propose_swap(&mut self, my_token: TokenId, their_token: TokenId) -> Result<U64, FansError> {
+        if env::attached_deposit().is_zero() {
+            env::panic_str("Requires attached deposit of at least 1 yoctoNEAR");
+        }
+        if self.paused {
+            return Err(FansError::Paused);
+        }
+        let proposer_id = env::predecessor_account_id();
+        let owner_id = self.tokens.owner_by_id.get(&my_token).ok_or(FansError::TokenNotFound)?;
+        if owner_id != proposer_id {
+            return Err(FansError::Unauthorized("Only the token owner can offer it in a swap".to_string()));
+        }
+        let counterparty_id = self.tokens.owner_by_id.get(&their_token).ok_or(FansError::TokenNotFound)?;
+        if counterparty_id == proposer_id {
+            return Err(FansError::InvalidSwap("both tokens belong to the caller".to_string()));
+        }
+
+        let deposit = env::attached_deposit().as_yoctonear();
+        let initial_storage = env::storage_usage();
+        let swap_id = self.next_swap_id;
+        let mut swap = Swap {
+            proposer_id: proposer_id.clone(),
+            token_id: my_token,
+            counterparty_id,
+            counterparty_token_id: their_token,
+            deposit: U128(0),
+            created_at: U64(env::block_timestamp()),
+        };
+        self.swaps.insert(&swap_id, &swap);
+        let required =
+            env::storage_usage().saturating_sub(initial_storage) as u128 * env::storage_byte_cost().as_yoctonear();
+        if deposit < required {
+            self.swaps.remove(&swap_id);
+            return Err(FansError::InsufficientDeposit { attached: U128(deposit), required: U128(required) });
+        }
+        swap.deposit = U128(required);
+        self.swaps.insert(&swap_id, &swap);
+        self.next_swap_id += 1;
+        if deposit > required {
+            Promise::new(proposer_id).transfer(NearToken::from_yoctonear(deposit - required));
+        }
+        SwapProposed {
+            swap_id: U64(swap_id),
+            proposer_id: swap.proposer_id,
+            token_id: swap.token_id,
+            counterparty_id: swap.counterparty_id,
+            counterparty_token_id: swap.counterparty_token_id,
+        }
+        .emit();
+        Ok(U64(swap_id))
+    }
+
+    /// Trades the two tokens of the swap. Both still have to be transferable under their own
+    /// lock rules, and each holder keeps a membership throughout. Requires 1 yoctoNEAR, like a
+    /// transfer.
+    #[payable]
+    #[handle_result]
+    pub fn accept_swap(&mut self, swap_id: U64) -> Result<(), FansError> {
+        assert_one_yocto();
+        if self.paused {
+            return Err(FansError::Paused);
+        }
+        let swap = self.swaps.get(&swap_id.0).ok_or(FansError::UnknownSwap)?;
+        let counterparty_id = env::predecessor_account_id();
+        if counterparty_id != swap.counterparty_id
+            || self.tokens.owner_by_id.get(&swap.counterparty_token_id).as_ref() != Some(&counterparty_id)
+        {
+            return Err(FansError::Unauthorized("Only the holder of the requested token can accept".to_string()));
+        }
+        let token = self.tokens.nft_token(swap.token_id.clone()).ok_or(FansError::TokenNotFound)?;
+        if token.owner_id != swap.proposer_id {
+            return Err(FansError::InvalidSwap("the proposer no longer holds the offered token".to_string()));
+        }
+        let counterparty_token = self.tokens.nft_token(swap.counterparty_token_id.clone()).ok_or(FansError::TokenNotFound)?;
+        self.internal_assert_not_banned(&swap.proposer_id)?;
+        self.internal_assert_not_banned(&counterparty_id)?;
+        self.assert_transferable(&token);
+        self.assert_transferable(&counterparty_token);
+
+        self.swaps.remove(&swap_id.0);
+        Promise::new(swap.proposer_id.clone()).transfer(NearToken::from_yoctonear(swap.deposit.0));
+        self.tokens.internal_transfer(&swap.proposer_id, &counterparty_id, &swap.token_id, None, None);
+        self.tokens.internal_transfer(&counterparty_id, &swap.proposer_id, &swap.counterparty_token_id, None, None);
+        self.internal_on_swap(&swap);
+        SwapAccepted { swap_id, proposer_id: swap.proposer_id, counterparty_id }.emit();
+        Ok(())
+    }
+
+    /// Withdraws or declines a swap, returning the proposal's storage deposit to the proposer
+    #[payable]
+    #[handle_result]
+    pub fn cancel_swap(&mut self, swap_id: U64) -> Result<(), FansError> {
+        let swap = self.swaps.get(&swap_id.0).ok_or(FansError::UnknownSwap)?;
+        let caller = env::predecessor_account_id();
+        if caller != swap.proposer_id && caller != swap.counterparty_id {
+            return Err(FansError::Unauthorized("Only the two parties can cancel a swap".to_string()));
+        }
+        self.swaps.remove(&swap_id.0);
+        Promise::new(swap.proposer_id).transfer(NearToken::from_yoctonear(swap.deposit.0));
+        SwapCancelled { swap_id, cancelled_by: caller }.emit();
+        Ok(())
+    }
+
+    pub fn get_swap(&self, swap_id: U64) -> Option<Swap> {
+        self.swaps.get(&swap_id.0)
+    }
+}
+
+impl Contract {
+    /// Resets `held_since` on both tokens. Memberships only move between groups when the two
+    /// tokens belong to different ones, and both accounts stay members in the registry.
+    fn internal_on_swap(&mut self, swap: &Swap) {
+        let mut extra = self.internal_token_extra(&self.tokens.nft_token(swap.token_id.clone()).unwrap());
+        let mut counterparty_extra =
+            self.internal_token_extra(&self.tokens.nft_token(swap.counterparty_token_id.clone()).unwrap());
+        extra.held_since = U64(env::block_timestamp());
+        counterparty_extra.held_since = U64(env::block_timestamp());
+        self.token_extra.insert(&swap.token_id, &extra);
+        self.token_extra.insert(&swap.counterparty_token_id, &counterparty_extra);
+        if extra.group_id != counterparty_extra.group_id {
+            self.internal_sync_membership(&extra.group_id, &swap.proposer_id, SyncAction::Revoke);
+            self.internal_sync_membership(&counterparty_extra.group_id, &swap.counterparty_id, SyncAction::Revoke);
+            self.internal_sync_membership(&extra.group_id, &swap.counterparty_id, SyncAction::Add);
+            self.internal_sync_membership(&counterparty_extra.group_id, &swap.proposer_id, SyncAction::Add);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+// Membership tiers, e.g. Bronze, Silver and Gold, each with its own cap, price, media and
+// transfer policy. A token's tier is recorded in its typed extra.
+use crate::events::{ConfigUpdated, TierUpgraded};
+use crate::membership::SyncAction;
+use crate::*;
+
+/// When the holder of a tier may move their token
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferPolicy {
+    /// One year after mint, like every token without a tier
+    #[default]
+    AfterLockup,
+    Anytime,
+    Never,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tier {
+    pub title: String,
+    /// Replaces the minted token's media when set
+    pub media: Option<String>,
+    /// sha256 of `media`, required with it
+    pub media_hash: Option<Base64VecU8>,
+    /// Live tokens the tier can hold at once; burned tokens free their slot
+    pub max_supply: u32,
+    /// Charged on top of the group price, in yoctoNEAR
+    pub price: U128,
+    pub transfer_policy: TransferPolicy,
+}
+
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TierView {
+    pub tier_id: String,
+    #[serde(flatten)]
+    pub tier: Tier,
+    pub supply: U128,
+}
+
+#[near]
+impl Contract {
+    /// Creates or replaces a tier. Lowering `max_supply` below the current supply only blocks
+    /// new mints.
+    #[payable]
+    #[handle_result]
+    pub fn set_tier(&mut self, tier_id: String, tier: Tier) -> Result<(), FansError> {
+        self.assert_owner("Only owner can set tiers")?;
+        Self::validate_media(&tier.media, &tier.media_hash)?;
+        self.tiers.insert(&tier_id, &tier);
+        if self.tier_supply.get(&tier_id).is_none() {
+            self.tier_supply.insert(&tier_id, &0);
+        }
+        ConfigUpdated { field: "tiers".to_string(), updated_by: env::predecessor_account_id() }.emit();
+        Ok(())
+    }
+
+    /// Moves the caller's token to a higher tier, charging the price difference plus any extra
+    /// storage. The token keeps its ID and `held_since`, and the devbot is told of the change.
+    #[payable]
+    #[handle_result]
+    pub fn upgrade_tier(&mut self, token_id: TokenId, new_tier: String) -> Result<Token, FansError> {
+        if self.paused {
+            return Err(FansError::Paused);
+        }
+        let token = self.tokens.nft_token(token_id.clone()).ok_or(FansError::TokenNotFound)?;
+        if env::predecessor_account_id() != token.owner_id {
+            return Err(FansError::Unauthorized("Only the token owner can upgrade its tier".to_string()));
+        }
+        let mut extra = self.internal_token_extra(&token);
+        if extra.tier.as_deref() == Some(new_tier.as_str()) {
+            return Err(FansError::NotAnUpgrade);
+        }
+        let tier = self.internal_tier_for_mint(&new_tier)?;
+        let old_price = extra.tier.as_ref().and_then(|tier_id| self.tiers.get(tier_id)).map_or(0, |tier| tier.price.0);
+        if tier.price.0 <= old_price {
+            return Err(FansError::NotAnUpgrade);
+        }
+        let deposit = env::attached_deposit().as_yoctonear();
+        if deposit < tier.price.0 - old_price {
+            return Err(FansError::InsufficientDeposit { attached: U128(deposit), required: U128(tier.price.0 - old_price) });
+        }
+
+        let initial_storage = env::storage_usage();
+        if let Some(old_tier) = &extra.tier {
+            self.internal_change_tier_supply(old_tier, false);
+        }
+        self.internal_change_tier_supply(&new_tier, true);
+        let old_tier = extra.tier.replace(new_tier.clone());
+        extra.mint_price = U128(extra.mint_price.0 + tier.price.0 - old_price);
+        self.token_extra.insert(&token_id, &extra);
+        let metadata_by_id = self.tokens.token_metadata_by_id.as_mut().expect("Metadata should exist");
+        if let Some(mut metadata) = metadata_by_id.get(&token_id) {
+            if tier.media.is_some() {
+                metadata.media = tier.media;
+                metadata.media_hash = tier.media_hash;
+            }
+            metadata.updated_at = Some(env::block_timestamp().to_string());
+            metadata_by_id.insert(&token_id, &metadata);
+        }
+        let storage_cost =
+            env::storage_usage().saturating_sub(initial_storage) as u128 * env::storage_byte_cost().as_yoctonear();
+        let sponsored = self.internal_sponsor_storage(&token.owner_id, storage_cost);
+        let required = tier.price.0 - old_price + storage_cost - sponsored;
+        if deposit < required {
+            return Err(FansError::InsufficientDeposit { attached: U128(deposit), required: U128(required) });
+        }
+        if deposit > required {
+            Promise::new(token.owner_id.clone()).transfer(NearToken::from_yoctonear(deposit - required));
+        }
+
+        let devbot_contract = self.devbot_contract.get().unwrap();
+        let budget = self.devbot_config.set_group_member_tier;
+        ext_devbot::ext(devbot_contract.clone())
+            .with_static_gas(budget.gas)
+            .with_attached_deposit(budget.deposit)
+            .set_group_member_tier(extra.group_id.clone(), token.owner_id.clone(), new_tier.clone())
+            .then(self.internal_sync_callback(&devbot_contract, &extra.group_id, &token.owner_id, SyncAction::SetTier));
+        TierUpgraded { token_id: token_id.clone(), owner_id: token.owner_id, old_tier, new_tier }.emit();
+        Ok(self.nft_token(token_id).unwrap())
+    }
+
+    pub fn get_tiers(&self) -> Vec<TierView> {
+        self.tiers.iter().map(|(tier_id, tier)| self.internal_tier_view(tier_id, tier)).collect()
+    }
+
+    /// Tier of `token_id`, `None` for tokens minted without one
+    pub fn get_tier(&self, token_id: TokenId) -> Option<TierView> {
+        let tier_id = self.token_extra.get(&token_id)?.tier?;
+        let tier = self.tiers.get(&tier_id)?;
+        Some(self.internal_tier_view(tier_id, tier))
+    }
+
+    pub fn get_tier_supply(&self, tier: String) -> U128 {
+        U128(self.tier_supply.get(&tier).unwrap_or(0) as u128)
+    }
+}
+
+impl Contract {
+    /// The tier a mint goes into, if it exists and still has room
+    pub(crate) fn internal_tier_for_mint(&self, tier_id: &str) -> Result<Tier, FansError> {
+        let tier = self.tiers.get(&tier_id.to_string()).ok_or_else(|| FansError::UnknownTier(tier_id.to_string()))?;
+        if self.tier_supply.get(&tier_id.to_string()).unwrap_or(0) >= tier.max_supply {
+            return Err(FansError::TierFull(tier_id.to_string()));
+        }
+        Ok(tier)
+    }
+
+    pub(crate) fn internal_change_tier_supply(&mut self, tier_id: &str, increase: bool) {
+        let supply = self.tier_supply.get(&tier_id.to_string()).unwrap_or(0);
+        let supply = if increase { supply + 1 } else { supply.saturating_sub(1) };
+        self.tier_supply.insert(&tier_id.to_string(), &supply);
+    }
+
+    /// Transfer policy of the token's tier; tokens without a tier keep the one-year lockup
+    pub(crate) fn internal_transfer_policy(&self, token_id: &TokenId) -> TransferPolicy {
+        self.token_extra
+            .get(token_id)
+            .and_then(|extra| extra.tier)
+            .and_then(|tier_id| self.tiers.get(&tier_id))
+            .map(|tier| tier.transfer_policy)
+            .unwrap_or_default()
+    }
+
+    fn internal_tier_view(&self, tier_id: String, tier: Tier) -> TierView {
+        TierView { supply: self.get_tier_supply(tier_id.clone()), tier_id, tier }
+    }
+}
@@ -0,0 +1,92 @@
+// Claim vouchers: agents sign mints off-chain and fans submit them, paying for their own mint,
+// so the agent never needs to send a transaction
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::CurveType;
+use std::convert::TryInto;
+
+/// A mint authorized by `agent_id`. The signature covers the borsh serialization of the
+/// voucher, and `contract_id` keeps a voucher from being replayed on another club.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Voucher {
+    pub contract_id: AccountId,
+    pub agent_id: AccountId,
+    pub account_id: AccountId,
+    pub group_id: String,
+    pub tier: Option<String>,
+    /// Block timestamp (ns) after which the voucher can no longer be claimed
+    pub expires_at: U64,
+    /// Chosen by the agent, each value can be claimed once
+    pub nonce: U64,
+}
+
+#[near]
+impl Contract {
+    /// Sets the ed25519 key the calling agent signs vouchers with, replacing any previous one
+    #[payable]
+    #[handle_result]
+    pub fn set_voucher_key(&mut self, public_key: PublicKey) -> Result<(), FansError> {
+        let agent_id = env::predecessor_account_id();
+        let mut agent = self
+            .internal_agent(&agent_id)
+            .ok_or_else(|| FansError::Unauthorized("Only authorized agents can set a voucher key".to_string()))?;
+        if public_key.curve_type() != CurveType::ED25519 {
+            return Err(FansError::InvalidVoucher("voucher key must be ed25519".to_string()));
+        }
+        agent.voucher_key = Some(public_key);
+        self.internal_save_agent(&agent_id, &agent);
+        Ok(())
+    }
+
+    /// Mints the voucher's token to the caller, who pays storage and prices like `nft_mint`.
+    /// The mint counts against the signing agent's quota.
+    #[payable]
+    #[handle_result]
+    pub fn claim_with_voucher(&mut self, voucher: Voucher, signature: Base64VecU8) -> Result<Promise, FansError> {
+        let caller = env::predecessor_account_id();
+        if voucher.contract_id != env::current_account_id() {
+            return Err(FansError::InvalidVoucher("voucher is for another contract".to_string()));
+        }
+        if voucher.account_id != caller {
+            return Err(FansError::InvalidVoucher("voucher is for another account".to_string()));
+        }
+        if env::block_timestamp() >= voucher.expires_at.0 {
+            return Err(FansError::InvalidVoucher("voucher has expired".to_string()));
+        }
+        let public_key = self
+            .internal_agent(&voucher.agent_id)
+            .and_then(|agent| agent.voucher_key)
+            .ok_or_else(|| FansError::InvalidVoucher("agent has no voucher key".to_string()))?;
+        let public_key: &[u8; 32] = public_key.as_bytes()[1..].try_into().unwrap();
+        let signature: &[u8; 64] = signature
+            .0
+            .as_slice()
+            .try_into()
+            .map_err(|_| FansError::InvalidVoucher("signature must be 64 bytes".to_string()))?;
+        if !env::ed25519_verify(signature, &borsh::to_vec(&voucher).unwrap(), public_key) {
+            return Err(FansError::InvalidVoucher("bad signature".to_string()));
+        }
+        let nonce = (voucher.agent_id.clone(), voucher.nonce.0);
+        if self.used_voucher_nonces.contains(&nonce) {
+            return Err(FansError::VoucherUsed);
+        }
+        let group = self.groups.get(&voucher.group_id).ok_or_else(|| FansError::UnknownGroup(voucher.group_id.clone()))?;
+        let token_metadata = Self::internal_group_metadata(group);
+        let promise = self.internal_request_mint(
+            &voucher.agent_id,
+            caller,
+            token_metadata,
+            voucher.group_id,
+            voucher.tier,
+            None,
+            Some(voucher.nonce),
+        )?;
+        self.used_voucher_nonces.insert(&nonce);
+        Ok(promise)
+    }
+
+    pub fn is_voucher_used(&self, agent_id: AccountId, nonce: U64) -> bool {
+        self.used_voucher_nonces.contains(&(agent_id, nonce.0))
+    }
+}
@@ -1,3 +1,5 @@
+pub mod relayer;
+
 use near_contract_standards::non_fungible_token::metadata::TokenMetadata;
 use near_contract_standards::non_fungible_token::TokenId;
 
@@ -0,0 +1,90 @@
+// A stand-in for a NEP-366 relayer: the fan signs a delegate action off-chain and the relayer
+// submits it in a transaction it pays for
+use near_crypto::SecretKey;
+use near_jsonrpc_client::methods::broadcast_tx_commit::RpcBroadcastTxCommitRequest;
+use near_jsonrpc_client::methods::query::RpcQueryRequest;
+use near_jsonrpc_client::JsonRpcClient;
+use near_jsonrpc_primitives::types::query::QueryResponseKind;
+use near_primitives::action::delegate::{DelegateAction, NonDelegateAction, SignedDelegateAction};
+use near_primitives::hash::CryptoHash;
+use near_primitives::transaction::{Action, FunctionCallAction, SignedTransaction, Transaction, TransactionV0};
+use near_primitives::types::{BlockReference, Finality};
+use near_primitives::views::{FinalExecutionOutcomeView, FinalExecutionStatus, QueryRequest};
+use near_sdk::serde_json::{self, Value};
+use near_workspaces::{Account, AccountId};
+use std::convert::TryFrom;
+
+const RELAYED_CALL_GAS: u64 = 200_000_000_000_000;
+
+pub struct Relayer {
+    client: JsonRpcClient,
+    account: Account,
+}
+
+impl Relayer {
+    pub fn new(rpc_addr: &str, account: Account) -> Self {
+        Self { client: JsonRpcClient::connect(rpc_addr), account }
+    }
+
+    pub fn id(&self) -> &AccountId {
+        self.account.id()
+    }
+
+    /// Calls `method` on `receiver_id` as `sender`, with no deposit and the gas paid by the relayer
+    pub async fn relay(&self, sender: &Account, receiver_id: &AccountId, method: &str, args: Value) -> anyhow::Result<FinalExecutionOutcomeView> {
+        let sender_key = secret_key(sender)?;
+        let (nonce, block_height, _) = self.access_key(sender.id(), &sender_key).await?;
+        let call = Action::FunctionCall(Box::new(FunctionCallAction {
+            method_name: method.to_string(),
+            args: serde_json::to_vec(&args)?,
+            gas: RELAYED_CALL_GAS,
+            deposit: 0,
+        }));
+        let delegate_action = DelegateAction {
+            sender_id: sender.id().clone(),
+            receiver_id: receiver_id.clone(),
+            actions: vec![NonDelegateAction::try_from(call).unwrap()],
+            nonce: nonce + 1,
+            max_block_height: block_height + 100,
+            public_key: sender_key.public_key(),
+        };
+        let signature = sender_key.sign(delegate_action.get_nep461_hash().as_ref());
+
+        let relayer_key = secret_key(&self.account)?;
+        let (nonce, _, block_hash) = self.access_key(self.account.id(), &relayer_key).await?;
+        let transaction = Transaction::V0(TransactionV0 {
+            signer_id: self.account.id().clone(),
+            public_key: relayer_key.public_key(),
+            nonce: nonce + 1,
+            receiver_id: sender.id().clone(),
+            block_hash,
+            actions: vec![Action::Delegate(Box::new(SignedDelegateAction { delegate_action, signature }))],
+        });
+        let signature = relayer_key.sign(transaction.get_hash_and_size().0.as_ref());
+        let signed_transaction = SignedTransaction::new(signature, transaction);
+        Ok(self.client.call(RpcBroadcastTxCommitRequest { signed_transaction }).await?)
+    }
+
+    /// Nonce of the account's key, with the latest block height and hash
+    async fn access_key(&self, account_id: &AccountId, key: &SecretKey) -> anyhow::Result<(u64, u64, CryptoHash)> {
+        let response = self
+            .client
+            .call(RpcQueryRequest {
+                block_reference: BlockReference::Finality(Finality::Final),
+                request: QueryRequest::ViewAccessKey { account_id: account_id.clone(), public_key: key.public_key() },
+            })
+            .await?;
+        match response.kind {
+            QueryResponseKind::AccessKey(access_key) => Ok((access_key.nonce, response.block_height, response.block_hash)),
+            _ => Err(anyhow::anyhow!("Unexpected response to an access key query")),
+        }
+    }
+}
+
+pub fn is_success(outcome: &FinalExecutionOutcomeView) -> bool {
+    matches!(outcome.status, FinalExecutionStatus::SuccessValue(_))
+}
+
+fn secret_key(account: &Account) -> anyhow::Result<SecretKey> {
+    Ok(account.secret_key().to_string().parse()?)
+}
@@ -0,0 +1,49 @@
+/*!
+A stub membership backend for e2e testing the membership fan-out. It keeps a member list per
+group under the method names `grant` and `revoke`, and can be told to fail every call. It also
+answers the devbot's group check, so it can stand in for the devbot when minting.
+*/
+use near_sdk::store::LookupSet;
+use near_sdk::{log, near, require, AccountId, PanicOnDefault};
+
+#[near(contract_state)]
+#[derive(PanicOnDefault)]
+pub struct MembershipBackend {
+    members: LookupSet<(String, AccountId)>,
+    failing: bool,
+}
+
+#[near]
+impl MembershipBackend {
+    #[init]
+    pub fn new() -> Self {
+        Self { members: LookupSet::new(b"m"), failing: false }
+    }
+
+    /// Makes every following `grant` and `revoke` panic, to simulate a backend that is down
+    pub fn set_failing(&mut self, failing: bool) {
+        self.failing = failing;
+    }
+
+    pub fn grant(&mut self, group_id: String, user_id: AccountId) {
+        require!(!self.failing, "backend unavailable");
+        log!("grant; group_id={}, user_id={}", group_id, user_id);
+        self.members.insert((group_id, user_id));
+    }
+
+    pub fn revoke(&mut self, group_id: String, user_id: AccountId) {
+        require!(!self.failing, "backend unavailable");
+        log!("revoke; group_id={}, user_id={}", group_id, user_id);
+        self.members.remove(&(group_id, user_id));
+    }
+
+    /// Every group exists as far as the stub is concerned
+    pub fn groups_contains_key(&self, group_id: String) -> bool {
+        log!("groups_contains_key; group_id={}", group_id);
+        true
+    }
+
+    pub fn is_member(&self, group_id: String, user_id: AccountId) -> bool {
+        self.members.contains(&(group_id, user_id))
+    }
+}
@@ -0,0 +1,86 @@
+use near_sdk::serde_json::json;
+use near_workspaces::types::NearToken;
+
+const GROUP_ID: &str = "default";
+const FIRST_HOLDER: &str = "1000fans.near";
+
+#[tokio::test]
+async fn membership() -> anyhow::Result<()> {
+    let nft_wasm = near_workspaces::compile_project(".").await?;
+    let backend_wasm = near_workspaces::compile_project("./tests/contracts/membership-backend").await?;
+    let worker = near_workspaces::sandbox().await?;
+
+    let backend = worker.dev_deploy(&backend_wasm).await?;
+    assert!(backend.call("new").transact().await?.is_success());
+    let nft_contract = worker.dev_deploy(&nft_wasm).await?;
+    let devbot_id = worker.dev_create_account().await?.id().clone();
+    let res = nft_contract.call("new").args_json(json!({ "devbot_contract": devbot_id })).transact().await?;
+    assert!(res.is_success());
+
+    // Swap the devbot for the stub, which has no register step
+    let res = nft_contract
+        .call("remove_membership_backend")
+        .args_json(json!({ "contract_id": devbot_id }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(res.is_success());
+    let res = nft_contract
+        .call("add_membership_backend")
+        .args_json(json!({ "backend": {
+            "contract_id": backend.id(),
+            "register_method": null,
+            "add_method": "grant",
+            "revoke_method": "revoke",
+            "register": { "gas": "0", "deposit": "0" },
+            "add": { "gas": "10000000000000", "deposit": "0" },
+            "revoke": { "gas": "10000000000000", "deposit": "0" },
+        }}))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(res.is_success());
+
+    // The first holder is granted on the stub
+    let res = nft_contract
+        .call("initialize")
+        .args_json(json!({}))
+        .max_gas()
+        .deposit(NearToken::from_millinear(11))
+        .transact()
+        .await?;
+    assert!(res.is_success());
+    let is_member: bool = backend
+        .view("is_member")
+        .args_json(json!({ "group_id": GROUP_ID, "user_id": FIRST_HOLDER }))
+        .await?
+        .json()?;
+    assert!(is_member);
+    let sync: near_sdk::serde_json::Value = nft_contract
+        .view("get_membership_sync")
+        .args_json(json!({ "account_id": FIRST_HOLDER }))
+        .await?
+        .json()?;
+    assert_eq!(sync[0]["status"]["action"], "add");
+    assert_eq!(sync[0]["status"]["success"], true);
+
+    // A failing backend is recorded, and the burn still goes through
+    assert!(backend.call("set_failing").args_json(json!({ "failing": true })).transact().await?.is_success());
+    let res = nft_contract
+        .call("nft_burn")
+        .args_json(json!({ "token_id": "fan000" }))
+        .max_gas()
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(res.is_success());
+    let sync: near_sdk::serde_json::Value = nft_contract
+        .view("get_membership_sync")
+        .args_json(json!({ "account_id": FIRST_HOLDER }))
+        .await?
+        .json()?;
+    assert_eq!(sync[0]["status"]["action"], "revoke");
+    assert_eq!(sync[0]["status"]["success"], false);
+
+    Ok(())
+}
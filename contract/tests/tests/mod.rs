@@ -1,3 +1,5 @@
 mod approval;
 mod enumeration;
 mod core;
+mod membership;
+mod sponsorship;
@@ -0,0 +1,86 @@
+use crate::common::relayer::{is_success, Relayer};
+use near_sdk::serde_json::json;
+use near_workspaces::types::NearToken;
+
+#[tokio::test]
+async fn sponsored_calls_through_relayer() -> anyhow::Result<()> {
+    let nft_wasm = near_workspaces::compile_project(".").await?;
+    let backend_wasm = near_workspaces::compile_project("./tests/contracts/membership-backend").await?;
+    let worker = near_workspaces::sandbox().await?;
+
+    // The stub backend doubles as the devbot, so mints go through
+    let backend = worker.dev_deploy(&backend_wasm).await?;
+    assert!(backend.call("new").transact().await?.is_success());
+    let nft_contract = worker.dev_deploy(&nft_wasm).await?;
+    let res = nft_contract.call("new").args_json(json!({ "devbot_contract": backend.id() })).transact().await?;
+    assert!(res.is_success());
+    let res = nft_contract
+        .call("initialize")
+        .args_json(json!({}))
+        .max_gas()
+        .deposit(NearToken::from_millinear(11))
+        .transact()
+        .await?;
+    assert!(res.is_success());
+
+    let fan = worker.dev_create_account().await?;
+    let stranger = worker.dev_create_account().await?;
+    let relayer = Relayer::new(&worker.rpc_addr(), worker.dev_create_account().await?);
+
+    // The owner funds the sponsor balance, mints the fan a token and then gives them two calls,
+    // so the mint pays its own storage
+    let res = nft_contract.call("fund_sponsorship").deposit(NearToken::from_near(1)).transact().await?;
+    assert!(res.is_success());
+    let res = nft_contract
+        .call("nft_mint")
+        .args_json(json!({
+            "token_owner_id": fan.id(),
+            "token_metadata": { "title": "1000fans Access Token" },
+            "group_id": "default",
+            "tier": null,
+        }))
+        .max_gas()
+        .deposit(NearToken::from_millinear(11))
+        .transact()
+        .await?;
+    assert!(res.is_success());
+    let res = nft_contract
+        .call("set_sponsored_calls")
+        .args_json(json!({ "account_id": fan.id(), "calls": 2 }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(res.is_success());
+
+    // A relayed approval costs the fan nothing, the sponsor pays its storage
+    let fan_balance = fan.view_account().await?.balance;
+    let outcome = relayer
+        .relay(&fan, nft_contract.id(), "nft_approve", json!({ "token_id": "fan001", "account_id": relayer.id() }))
+        .await?;
+    assert!(is_success(&outcome), "{:?}", outcome.status);
+    let approved: bool = nft_contract
+        .view("nft_is_approved")
+        .args_json(json!({ "token_id": "fan001", "approved_account_id": relayer.id() }))
+        .await?
+        .json()?;
+    assert!(approved);
+    let calls: u32 = nft_contract.view("get_sponsored_calls").args_json(json!({ "account_id": fan.id() })).await?.json()?;
+    assert_eq!(calls, 1);
+    assert_eq!(fan.view_account().await?.balance, fan_balance);
+
+    // Burning needs no deposit at all, and starts the grace period
+    let outcome = relayer.relay(&fan, nft_contract.id(), "nft_burn", json!({ "token_id": "fan001" })).await?;
+    assert!(is_success(&outcome), "{:?}", outcome.status);
+    let pending_burn: near_sdk::serde_json::Value =
+        nft_contract.view("get_pending_burn").args_json(json!({ "token_id": "fan001" })).await?.json()?;
+    assert_eq!(pending_burn["owner_id"], fan.id().as_str());
+    assert_eq!(fan.view_account().await?.balance, fan_balance);
+
+    // Without sponsored calls the usual deposit is required
+    let outcome = relayer
+        .relay(&stranger, nft_contract.id(), "nft_approve", json!({ "token_id": "fan000", "account_id": relayer.id() }))
+        .await?;
+    assert!(!is_success(&outcome));
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+// Errors returned by the club factory
+use near_sdk::json_types::U128;
+use near_sdk::{near, AccountId, FunctionError};
+use std::fmt;
+
+/// Every failure surfaced by the factory, read as `<CODE>: <message>` like the club's own
+/// errors. Clients should match on the code.
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq, FunctionError)]
+pub enum FactoryError {
+    /// `UNAUTHORIZED`: the caller is not the factory owner
+    Unauthorized(String),
+    /// `NO_CODE`: no club wasm has been stored yet
+    NoCode,
+    /// `INVALID_CLUB_NAME`: the name does not make a valid subaccount of the factory
+    InvalidClubName(String),
+    /// `CLUB_EXISTS`: a club with this account is already registered
+    ClubExists(AccountId),
+    /// `INSUFFICIENT_DEPOSIT`: the attached deposit does not cover the club, in yoctoNEAR
+    InsufficientDeposit { attached: U128, required: U128 },
+}
+
+impl FactoryError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            FactoryError::Unauthorized(_) => "UNAUTHORIZED",
+            FactoryError::NoCode => "NO_CODE",
+            FactoryError::InvalidClubName(_) => "INVALID_CLUB_NAME",
+            FactoryError::ClubExists(_) => "CLUB_EXISTS",
+            FactoryError::InsufficientDeposit { .. } => "INSUFFICIENT_DEPOSIT",
+        }
+    }
+}
+
+impl fmt::Display for FactoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        match self {
+            FactoryError::Unauthorized(reason) => write!(f, "{}", reason),
+            FactoryError::NoCode => write!(f, "No club code has been stored"),
+            FactoryError::InvalidClubName(name) => write!(f, "{} is not a valid club name", name),
+            FactoryError::ClubExists(club_id) => write!(f, "Club {} already exists", club_id),
+            FactoryError::InsufficientDeposit { attached, required } => {
+                write!(f, "Insufficient deposit: attached {}, required {}", attached.0, required.0)
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Events for the `1000fans_factory` standard, logged in the NEP-297 format.
+use near_sdk::serde::Serialize;
+use near_sdk::{near, AccountId};
+
+/// A club was deployed, initialized and registered
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ClubCreated {
+    pub club_id: AccountId,
+    pub owner_id: AccountId,
+}
+
+/// A club now runs `code_version` of the stored wasm
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ClubUpgraded {
+    pub club_id: AccountId,
+    pub code_version: u32,
+}
+
+#[near(event_json(standard = "1000fans_factory"))]
+pub enum FactoryEvent {
+    #[event_version("1.0.0")]
+    ClubCreated(ClubCreated),
+    #[event_version("1.0.0")]
+    ClubUpgraded(ClubUpgraded),
+}
+
+macro_rules! impl_emit {
+    ($($name:ident),*) => {
+        $(
+            impl $name {
+                /// Logs the event to the host
+                pub fn emit(self) {
+                    FactoryEvent::$name(self).emit()
+                }
+            }
+        )*
+    };
+}
+
+impl_emit!(ClubCreated, ClubUpgraded);
@@ -0,0 +1,474 @@
+// A factory to deploy 1000fans clubs as subaccounts, e.g. theosis.1000fans.near, and to keep
+// them on the latest club code
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde_json::json;
+use near_sdk::{env, near, serde_json, AccountId, BorshStorageKey, CryptoHash, Gas, NearToken, PanicOnDefault, Promise, PromiseResult};
+
+mod error;
+pub mod events;
+
+pub use crate::error::FactoryError;
+use crate::events::{ClubCreated, ClubUpgraded};
+
+const NEW_CLUB_GAS: Gas = Gas::from_tgas(20);
+/// `initialize` mints fan000 and registers the club's group on the devbot
+const INITIALIZE_GAS: Gas = Gas::from_tgas(120);
+/// The club's `upgrade` deploys the code and runs its `migrate` in the same batch, out of this
+const UPGRADE_GAS: Gas = Gas::from_tgas(100);
+const CALLBACK_GAS: Gas = Gas::from_tgas(10);
+/// Storage for fan000, attached to `initialize`
+const INITIALIZE_DEPOSIT: NearToken = NearToken::from_millinear(11); // 0.011 NEAR
+/// Left on the club on top of its code storage, to pay for state and devbot calls
+const MIN_CLUB_BALANCE: NearToken = NearToken::from_millinear(500); // 0.5 NEAR
+
+/// A club deployed by the factory
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Club {
+    pub club_id: AccountId,
+    pub name: String,
+    pub symbol: String,
+    pub max_supply: u32,
+    pub owner_id: AccountId,
+    pub devbot: AccountId,
+    pub created_at: U64,
+    /// Version of the stored wasm the club last received
+    pub code_version: u32,
+}
+
+#[derive(PanicOnDefault)]
+#[near(contract_state)]
+pub struct ClubFactory {
+    owner_id: AccountId,
+    code: LazyOption<Vec<u8>>,
+    code_version: u32,
+    clubs: UnorderedMap<AccountId, Club>,
+    /// Registry: clubs each account holds a token of, and the reverse
+    clubs_of: LookupMap<AccountId, UnorderedSet<AccountId>>,
+    members_of: LookupMap<AccountId, UnorderedSet<AccountId>>,
+}
+
+#[derive(BorshStorageKey)]
+#[near]
+enum StorageKey {
+    Code,
+    Clubs,
+    ClubsOf,
+    ClubsOfInner { account_hash: CryptoHash },
+    MembersOf,
+    MembersOfInner { club_hash: CryptoHash },
+}
+
+#[near]
+impl ClubFactory {
+    #[init]
+    pub fn new(owner_id: AccountId) -> Self {
+        Self {
+            owner_id,
+            code: LazyOption::new(StorageKey::Code, None),
+            code_version: 0,
+            clubs: UnorderedMap::new(StorageKey::Clubs),
+            clubs_of: LookupMap::new(StorageKey::ClubsOf),
+            members_of: LookupMap::new(StorageKey::MembersOf),
+        }
+    }
+
+    /// Stores the club wasm passed as raw input and returns its version. Clubs created from
+    /// now on get it; existing ones through `upgrade_clubs`.
+    #[handle_result]
+    pub fn set_club_code(&mut self) -> Result<u32, FactoryError> {
+        self.assert_owner("Only owner can set the club code")?;
+        let code = env::input().filter(|code| !code.is_empty()).ok_or(FactoryError::NoCode)?;
+        self.code.set(&code);
+        self.code_version += 1;
+        Ok(self.code_version)
+    }
+
+    pub fn get_code_version(&self) -> u32 {
+        self.code_version
+    }
+
+    /// Deposit `create_club` needs: the club's code storage, its starting balance and fan000
+    #[handle_result]
+    pub fn get_club_cost(&self) -> Result<U128, FactoryError> {
+        let code_len = self.code.get().ok_or(FactoryError::NoCode)?.len() as u128;
+        Ok(U128(
+            code_len * env::storage_byte_cost().as_yoctonear()
+                + MIN_CLUB_BALANCE.as_yoctonear()
+                + INITIALIZE_DEPOSIT.as_yoctonear(),
+        ))
+    }
+
+    /// Creates `<name>.<factory>`, deploys the club code, then calls `new_club` and
+    /// `initialize`. The club is registered once all of it went through; otherwise the
+    /// deposit is refunded.
+    #[payable]
+    #[handle_result]
+    pub fn create_club(
+        &mut self,
+        name: String,
+        symbol: String,
+        max_supply: u32,
+        devbot: AccountId,
+        owner: AccountId,
+    ) -> Result<Promise, FactoryError> {
+        let code = self.code.get().ok_or(FactoryError::NoCode)?;
+        if name.contains('.') {
+            return Err(FactoryError::InvalidClubName(name));
+        }
+        let club_id: AccountId = format!("{}.{}", name, env::current_account_id())
+            .parse()
+            .map_err(|_| FactoryError::InvalidClubName(name.clone()))?;
+        if self.clubs.get(&club_id).is_some() {
+            return Err(FactoryError::ClubExists(club_id));
+        }
+        let deposit = env::attached_deposit();
+        let required = self.get_club_cost()?;
+        if deposit.as_yoctonear() < required.0 {
+            return Err(FactoryError::InsufficientDeposit { attached: U128(deposit.as_yoctonear()), required });
+        }
+        let club = Club {
+            club_id: club_id.clone(),
+            name: name.clone(),
+            symbol: symbol.clone(),
+            max_supply,
+            owner_id: owner.clone(),
+            devbot: devbot.clone(),
+            created_at: U64(env::block_timestamp()),
+            code_version: self.code_version,
+        };
+        let new_args = json!({
+            "devbot_contract": devbot,
+            "name": name,
+            "symbol": symbol,
+            "max_supply": max_supply,
+            "owner_id": owner,
+            "factory_id": env::current_account_id(),
+        });
+        Ok(Promise::new(club_id)
+            .create_account()
+            .transfer(deposit.saturating_sub(INITIALIZE_DEPOSIT))
+            .deploy_contract(code)
+            .function_call("new_club".to_string(), new_args.to_string().into_bytes(), NearToken::from_yoctonear(0), NEW_CLUB_GAS)
+            .function_call("initialize".to_string(), b"{}".to_vec(), INITIALIZE_DEPOSIT, INITIALIZE_GAS)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(CALLBACK_GAS)
+                    .on_club_created(club, env::predecessor_account_id(), U128(deposit.as_yoctonear())),
+            ))
+    }
+
+    #[private]
+    pub fn on_club_created(&mut self, club: Club, creator_id: AccountId, deposit: U128) -> bool {
+        match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                self.clubs.insert(&club.club_id, &club);
+                // `initialize` returns fan000, whose holder is the club's first member
+                let first_holder = serde_json::from_slice::<serde_json::Value>(&value)
+                    .ok()
+                    .and_then(|token| token.get("owner_id")?.as_str()?.parse::<AccountId>().ok());
+                if let Some(account_id) = first_holder {
+                    self.internal_record_membership(&club.club_id, &account_id, true);
+                }
+                ClubCreated { club_id: club.club_id, owner_id: club.owner_id }.emit();
+                true
+            }
+            // The batch is atomic, so the account was not created and the deposit came back
+            _ => {
+                Promise::new(creator_id).transfer(NearToken::from_yoctonear(deposit.0));
+                false
+            }
+        }
+    }
+
+    /// Sends the stored code to up to `limit` clubs, starting at `from_index`, that run an
+    /// older version. Each club migrates its own state after the deploy, `migrate` being
+    /// private to it. Paginated because every call carries the whole wasm. Returns the
+    /// number of upgrades started.
+    #[handle_result]
+    pub fn upgrade_clubs(&mut self, from_index: Option<u64>, limit: Option<u64>) -> Result<u32, FactoryError> {
+        self.assert_owner("Only owner can upgrade clubs")?;
+        let code = self.code.get().ok_or(FactoryError::NoCode)?;
+        let mut started = 0;
+        for club in self.get_clubs(from_index, limit) {
+            if club.code_version >= self.code_version {
+                continue;
+            }
+            Promise::new(club.club_id.clone())
+                .function_call("upgrade".to_string(), code.clone(), NearToken::from_yoctonear(0), UPGRADE_GAS)
+                .then(
+                    Self::ext(env::current_account_id())
+                        .with_static_gas(CALLBACK_GAS)
+                        .on_club_upgraded(club.club_id, self.code_version),
+                );
+            started += 1;
+        }
+        Ok(started)
+    }
+
+    #[private]
+    pub fn on_club_upgraded(&mut self, club_id: AccountId, code_version: u32) -> bool {
+        let upgraded = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if let Some(mut club) = self.clubs.get(&club_id).filter(|_| upgraded) {
+            club.code_version = code_version;
+            self.clubs.insert(&club_id, &club);
+            ClubUpgraded { club_id, code_version }.emit();
+        }
+        upgraded
+    }
+
+    pub fn get_club(&self, club_id: AccountId) -> Option<Club> {
+        self.clubs.get(&club_id)
+    }
+
+    pub fn get_clubs(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<Club> {
+        self.clubs
+            .values()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .collect()
+    }
+
+    pub fn get_club_count(&self) -> u64 {
+        self.clubs.len()
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    /// Called by a registered club when `account_id` gets (`member`) or loses a token
+    #[handle_result]
+    pub fn record_membership(&mut self, account_id: AccountId, member: bool) -> Result<(), FactoryError> {
+        let club_id = env::predecessor_account_id();
+        if self.clubs.get(&club_id).is_none() {
+            return Err(FactoryError::Unauthorized("Only registered clubs can record members".to_string()));
+        }
+        self.internal_record_membership(&club_id, &account_id, member);
+        Ok(())
+    }
+
+    pub fn clubs_of(&self, account_id: AccountId, from_index: Option<u64>, limit: Option<u64>) -> Vec<AccountId> {
+        Self::internal_page(self.clubs_of.get(&account_id), from_index, limit)
+    }
+
+    pub fn members_of(&self, club: AccountId, from_index: Option<u64>, limit: Option<u64>) -> Vec<AccountId> {
+        Self::internal_page(self.members_of.get(&club), from_index, limit)
+    }
+}
+
+impl ClubFactory {
+    fn internal_record_membership(&mut self, club_id: &AccountId, account_id: &AccountId, member: bool) {
+        let clubs = self.clubs_of.get(account_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::ClubsOfInner { account_hash: env::sha256_array(account_id.as_bytes()) })
+        });
+        let members = self.members_of.get(club_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::MembersOfInner { club_hash: env::sha256_array(club_id.as_bytes()) })
+        });
+        Self::internal_update_set(&mut self.clubs_of, account_id, clubs, club_id, member);
+        Self::internal_update_set(&mut self.members_of, club_id, members, account_id, member);
+    }
+
+    /// Adds or removes `value` in the set stored under `key`, dropping sets that end up empty
+    fn internal_update_set(
+        map: &mut LookupMap<AccountId, UnorderedSet<AccountId>>,
+        key: &AccountId,
+        mut set: UnorderedSet<AccountId>,
+        value: &AccountId,
+        insert: bool,
+    ) {
+        if insert {
+            set.insert(value);
+        } else {
+            set.remove(value);
+        }
+        if set.is_empty() {
+            map.remove(key);
+        } else {
+            map.insert(key, &set);
+        }
+    }
+
+    fn internal_page(set: Option<UnorderedSet<AccountId>>, from_index: Option<u64>, limit: Option<u64>) -> Vec<AccountId> {
+        set.map_or_else(Vec::new, |set| {
+            set.iter()
+                .skip(from_index.unwrap_or(0) as usize)
+                .take(limit.map_or(usize::MAX, |limit| limit as usize))
+                .collect()
+        })
+    }
+
+    fn assert_owner(&self, reason: &str) -> Result<(), FactoryError> {
+        if env::predecessor_account_id() != self.owner_id {
+            return Err(FactoryError::Unauthorized(reason.to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const CODE: &[u8] = b"\0asm club code";
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id("1000fans.near".parse().unwrap())
+            .signer_account_id(predecessor_account_id.clone())
+            .predecessor_account_id(predecessor_account_id)
+            .account_balance(NearToken::from_near(100));
+        builder
+    }
+
+    fn sample_club(club_id: &str, code_version: u32) -> Club {
+        Club {
+            club_id: club_id.parse().unwrap(),
+            name: "theosis".to_string(),
+            symbol: "THEO".to_string(),
+            max_supply: 1000,
+            owner_id: accounts(1),
+            devbot: "theosis.devbot.near".parse().unwrap(),
+            created_at: U64(0),
+            code_version,
+        }
+    }
+
+    fn with_input(context: &mut VMContextBuilder, input: Vec<u8>) {
+        let mut vm_context = context.build();
+        vm_context.input = input;
+        testing_env!(vm_context);
+    }
+
+    fn with_promise_result(context: &mut VMContextBuilder, result: PromiseResult) {
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result]
+        );
+    }
+
+    #[test]
+    fn test_create_club() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut factory = ClubFactory::new(accounts(0));
+
+        // Fail: nothing to deploy yet
+        let result = factory.create_club("theosis".to_string(), "THEO".to_string(), 1000, accounts(2), accounts(1));
+        assert_eq!(result.err().unwrap(), FactoryError::NoCode);
+
+        with_input(&mut context, CODE.to_vec());
+        assert_eq!(factory.set_club_code().unwrap(), 1);
+        let cost = factory.get_club_cost().unwrap();
+        assert_eq!(
+            cost.0,
+            CODE.len() as u128 * env::storage_byte_cost().as_yoctonear()
+                + MIN_CLUB_BALANCE.as_yoctonear()
+                + INITIALIZE_DEPOSIT.as_yoctonear()
+        );
+
+        // Fail: invalid name, or not enough to pay for the club
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(NearToken::from_yoctonear(cost.0)).build());
+        let result = factory.create_club("the.osis".to_string(), "THEO".to_string(), 1000, accounts(2), accounts(1));
+        assert_eq!(result.err().unwrap(), FactoryError::InvalidClubName("the.osis".to_string()));
+        let result = factory.create_club("Theosis!".to_string(), "THEO".to_string(), 1000, accounts(2), accounts(1));
+        assert_eq!(result.err().unwrap(), FactoryError::InvalidClubName("Theosis!".to_string()));
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(cost.0 - 1)).build());
+        let result = factory.create_club("theosis".to_string(), "THEO".to_string(), 1000, accounts(2), accounts(1));
+        assert!(matches!(result.err().unwrap(), FactoryError::InsufficientDeposit { .. }));
+
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(cost.0)).build());
+        assert!(factory
+            .create_club("theosis".to_string(), "THEO".to_string(), 1000, accounts(2), accounts(1))
+            .is_ok());
+
+        // The club is only registered once the deployment went through
+        with_promise_result(&mut context, PromiseResult::Failed);
+        assert!(!factory.on_club_created(sample_club("theosis.1000fans.near", 1), accounts(1), cost));
+        assert_eq!(factory.get_club_count(), 0);
+        with_promise_result(&mut context, PromiseResult::Successful(vec![]));
+        assert!(factory.on_club_created(sample_club("theosis.1000fans.near", 1), accounts(1), cost));
+        assert_eq!(factory.get_club("theosis.1000fans.near".parse().unwrap()), Some(sample_club("theosis.1000fans.near", 1)));
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"1000fans_factory","version":"1.0.0","event":"club_created","data":{"club_id":"theosis.1000fans.near","owner_id":"bob"}}"#]
+        );
+
+        // Fail: the club already exists
+        testing_env!(context.build());
+        let result = factory.create_club("theosis".to_string(), "THEO".to_string(), 1000, accounts(2), accounts(1));
+        assert_eq!(result.err().unwrap(), FactoryError::ClubExists("theosis.1000fans.near".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_upgrade_clubs() {
+        let mut context = get_context(accounts(0));
+        with_input(&mut context, CODE.to_vec());
+        let mut factory = ClubFactory::new(accounts(0));
+        factory.set_club_code().unwrap();
+        for name in ["a", "b", "c"] {
+            let club = sample_club(&format!("{}.1000fans.near", name), 1);
+            factory.clubs.insert(&club.club_id.clone(), &club);
+        }
+        assert_eq!(factory.get_clubs(Some(1), Some(1))[0].club_id.to_string(), "b.1000fans.near");
+
+        // Every club already runs version 1
+        with_input(&mut context, vec![]);
+        assert_eq!(factory.upgrade_clubs(None, None).unwrap(), 0);
+
+        with_input(&mut context, b"\0asm new club code".to_vec());
+        assert_eq!(factory.set_club_code().unwrap(), 2);
+        with_input(&mut context, vec![]);
+        assert_eq!(factory.upgrade_clubs(Some(0), Some(2)).unwrap(), 2);
+        with_promise_result(&mut context, PromiseResult::Successful(vec![]));
+        assert!(factory.on_club_upgraded("a.1000fans.near".parse().unwrap(), 2));
+        with_promise_result(&mut context, PromiseResult::Failed);
+        assert!(!factory.on_club_upgraded("b.1000fans.near".parse().unwrap(), 2));
+        let versions: Vec<u32> = factory.get_clubs(None, None).iter().map(|club| club.code_version).collect();
+        assert_eq!(versions, vec![2, 1, 1]);
+
+        // Fail: only the owner sets code or upgrades
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert!(matches!(factory.upgrade_clubs(None, None).err().unwrap(), FactoryError::Unauthorized(_)));
+        assert!(matches!(factory.set_club_code().err().unwrap(), FactoryError::Unauthorized(_)));
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut factory = ClubFactory::new(accounts(0));
+        let fan000 = br#"{"token_id":"fan000","owner_id":"1000fans.near","metadata":null,"approved_account_ids":{}}"#;
+        for club_id in ["a.1000fans.near", "b.1000fans.near"] {
+            with_promise_result(&mut context, PromiseResult::Successful(fan000.to_vec()));
+            factory.on_club_created(sample_club(club_id, 1), accounts(1), U128(0));
+        }
+        assert_eq!(factory.clubs_of("1000fans.near".parse().unwrap(), None, None).len(), 2);
+
+        // Clubs report their members as tokens are minted, burned and transferred
+        testing_env!(context.predecessor_account_id("a.1000fans.near".parse().unwrap()).build());
+        factory.record_membership(accounts(1), true).unwrap();
+        testing_env!(context.predecessor_account_id("b.1000fans.near".parse().unwrap()).build());
+        factory.record_membership(accounts(1), true).unwrap();
+        factory.record_membership(accounts(2), true).unwrap();
+        let clubs: Vec<String> = factory.clubs_of(accounts(1), None, None).iter().map(|id| id.to_string()).collect();
+        assert_eq!(clubs, vec!["a.1000fans.near", "b.1000fans.near"]);
+        assert_eq!(factory.members_of("b.1000fans.near".parse().unwrap(), Some(1), Some(2)), vec![accounts(1), accounts(2)]);
+
+        factory.record_membership(accounts(1), false).unwrap();
+        assert_eq!(factory.clubs_of(accounts(1), None, None), vec!["a.1000fans.near".parse::<AccountId>().unwrap()]);
+        factory.record_membership(accounts(2), false).unwrap();
+        assert!(factory.clubs_of(accounts(2), None, None).is_empty());
+
+        // Fail: only registered clubs report members
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert!(matches!(factory.record_membership(accounts(3), true).err().unwrap(), FactoryError::Unauthorized(_)));
+    }
+}
@@ -156,7 +156,7 @@ export default async function handler(req, res) {
       };
       const groupId = "theosis";
       const contractId = "theosis.1000fans.near";
-      const mintDeposit = utils.format.parseNearAmount("0.06"); // 0.007 for mint + 0.007 for callback + 0.025 for add_group_member
+      const mintDeposit = utils.format.parseNearAmount("0.06"); // 0.011 for mint storage plus any group price; the contract refunds the rest
 
       const mintResult = await relayerAccount.functionCall({
         contractId,
@@ -166,7 +166,7 @@ export default async function handler(req, res) {
           token_metadata: tokenMetadata,
           group_id: groupId,
         },
-        gas: "100000000000000",
+        gas: "150000000000000", // 150 TGas: group check, nft_mint_callback with add_group_member, on_mint_resolved
         attachedDeposit: mintDeposit,
       });
 
@@ -176,9 +176,11 @@ export default async function handler(req, res) {
 
       // Extract token ID
       if (mintResult.status?.SuccessValue) {
+        const decodedValue = Buffer.from(mintResult.status.SuccessValue, 'base64').toString();
+        console.log('Decoded mint result:', decodedValue);
+        // nft_mint resolves to null when the mint failed and the deposit was refunded
+        if (decodedValue === 'null') throw new Error('Mint failed: nft_mint returned no token');
         try {
-          const decodedValue = Buffer.from(mintResult.status.SuccessValue, 'base64').toString();
-          console.log('Decoded mint result:', decodedValue);
           const token = JSON.parse(decodedValue);
           tokenId = token.token_id;
           if (!tokenId) throw new Error('Token ID missing in mint result');